@@ -0,0 +1,95 @@
+//! Generates synthetic watch histories, so the benchmarks don't depend on
+//! anyone's real export.
+
+use std::fmt::Write;
+
+use chrono::{Duration, FixedOffset, TimeZone};
+
+const CHANNELS: usize = 200;
+const VIDEOS: usize = 5000;
+
+/// A watch in the generated history, newest first like Takeout.
+pub struct Row {
+    video: usize,
+    channel: usize,
+    when: chrono::DateTime<FixedOffset>,
+}
+
+/// `count` watches spread over a few years, picked with a fixed seed so every
+/// run parses the same input.
+pub fn rows(count: usize) -> Vec<Row> {
+    let mut seed: u64 = 1;
+    let mut next = |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (seed >> 33) % n
+    };
+
+    let edt = FixedOffset::west_opt(4 * 3600).unwrap();
+    let mut when = edt.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+
+    (0..count)
+        .map(|_| {
+            let video = next(VIDEOS as u64) as usize;
+            when -= Duration::minutes(1 + next(180) as i64);
+            Row {
+                video,
+                channel: video % CHANNELS,
+                when,
+            }
+        })
+        .collect()
+}
+
+fn video_url(video: usize) -> String {
+    format!("https://www.youtube.com/watch?v=vid{video:08}")
+}
+
+fn channel_url(channel: usize) -> String {
+    format!("https://www.youtube.com/channel/UCbench{channel:017}")
+}
+
+/// The rows in the HTML export's format.
+pub fn html(rows: &[Row]) -> String {
+    let mut html = String::from("<html><body>\n");
+
+    for row in rows {
+        // U+00A0 is a non-breaking space, U+202F a narrow one
+        writeln!(
+            html,
+            "<div class=\"content-cell\">Watched\u{00A0}<a href=\"{}\">Video {} &amp; friends</a><br /><a href=\"{}\">Channel {}</a><br />{}\u{202F}{} EDT\n</div>",
+            video_url(row.video),
+            row.video,
+            channel_url(row.channel),
+            row.channel,
+            row.when.format("%b %-d, %Y, %-I:%M:%S"),
+            row.when.format("%p"),
+        )
+        .unwrap();
+    }
+
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// The rows in the JSON export's format.
+pub fn json(rows: &[Row]) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            serde_json::json!({
+                "header": "YouTube",
+                "title": format!("Watched Video {} & friends", row.video),
+                "titleUrl": video_url(row.video),
+                "subtitles": [{
+                    "name": format!("Channel {}", row.channel),
+                    "url": channel_url(row.channel),
+                }],
+                "time": row.when.with_timezone(&chrono::Utc).to_rfc3339(),
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&rows).unwrap()
+}
@@ -0,0 +1,53 @@
+//! Parsing throughput on generated histories. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+// The crate is a binary, so include the modules the parser needs directly
+#[path = "../../src/model.rs"]
+#[allow(dead_code, unused_imports)]
+mod model;
+#[path = "../../src/parser/mod.rs"]
+#[allow(dead_code, unused_imports)]
+mod parser;
+#[path = "../../src/utf8_reader.rs"]
+#[allow(dead_code, unused_imports)]
+mod utf8_reader;
+
+mod generate;
+
+use parser::{ParseOptions, ParserType};
+
+const ROWS: usize = 20_000;
+
+fn parse(c: &mut Criterion) {
+    let rows = generate::rows(ROWS);
+    let html = generate::html(&rows);
+    let json = generate::json(&rows);
+
+    let mut group = c.benchmark_group("parse");
+    // Reported as rows per second
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.sample_size(20);
+
+    group.bench_function("html", |b| {
+        b.iter(|| {
+            let (models, _) =
+                parser::parser(ParserType::Html, html.as_bytes(), &ParseOptions::default())
+                    .unwrap();
+            assert_eq!(models.watches().len(), ROWS);
+        })
+    });
+    group.bench_function("json", |b| {
+        b.iter(|| {
+            let (models, _) =
+                parser::parser(ParserType::Json, json.as_bytes(), &ParseOptions::default())
+                    .unwrap();
+            assert_eq!(models.watches().len(), ROWS);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
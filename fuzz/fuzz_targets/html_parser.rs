@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/model.rs"]
+#[allow(dead_code)]
+mod model;
+#[path = "../../src/parser/mod.rs"]
+#[allow(dead_code, unused_imports)]
+mod parser;
+#[path = "../../src/utf8_reader.rs"]
+#[allow(dead_code)]
+mod utf8_reader;
+
+use parser::{ModelsParser, ParseOptions};
+use utf8_reader::Utf8Iter;
+
+// Any input should produce Ok or Err, never a panic.
+fuzz_target!(|data: &str| {
+    let mut parser = ModelsParser::new(&ParseOptions::default());
+    let _ = parser.parse(Utf8Iter::new(data.as_bytes()));
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/utf8_reader.rs"]
+#[allow(dead_code)]
+mod utf8_reader;
+
+use utf8_reader::{Error, Utf8Iter};
+
+// Every call to next() must consume at least one byte, and together the
+// characters and invalid bytes must account for the whole input.
+fuzz_target!(|data: &[u8]| {
+    let mut iter = Utf8Iter::new(data);
+    let mut consumed = 0;
+
+    loop {
+        match iter.next() {
+            Ok(c) => consumed += c.len_utf8(),
+            Err(Error::InvalidBytes(bytes)) => {
+                assert!(!bytes.is_empty(), "InvalidBytes without any bytes");
+                consumed += bytes.len();
+            }
+            Err(Error::End) => break,
+            Err(Error::IoError(e)) => panic!("reading a slice can't fail: {}", e),
+        }
+
+        assert!(consumed <= data.len(), "read past the end of the input");
+    }
+
+    assert_eq!(consumed, data.len());
+});
@@ -0,0 +1,220 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::model::{Channel, Models, WhereVideo};
+use crate::report::ChannelCount;
+
+/// Overlap between two histories, `a` and `b`. Like `Report`, the console
+/// output and `--format json` are both rendered from this struct.
+#[derive(Serialize, Debug)]
+pub struct Comparison {
+    pub a_videos: usize,
+    pub b_videos: usize,
+    pub common_videos: usize,
+    pub a_channels: usize,
+    pub b_channels: usize,
+    pub common_channels: usize,
+    /// Shared channels, ranked by the watches of both histories combined.
+    pub top_common_channels: Vec<ChannelCount>,
+    /// Channels only `a` watched.
+    pub top_a_only_channels: Vec<ChannelCount>,
+    /// Channels only `b` watched.
+    pub top_b_only_channels: Vec<ChannelCount>,
+    /// Shared channels divided by all channels watched by either history.
+    pub channel_jaccard: f64,
+    pub video_jaccard: f64,
+}
+
+impl Comparison {
+    /// Compares two histories, keeping `top` entries in each channel list.
+    pub fn new(a: &Models, b: &Models, top: usize) -> Comparison {
+        let a_channels = a.top_channels(usize::MAX);
+        let b_channels = b.top_channels(usize::MAX);
+        let common_videos = a.common_videos(b).len();
+        let (_, a_only) = a.unique_to(b);
+        let (_, b_only) = b.unique_to(a);
+        let a_videos = a.count_videos(WhereVideo::Any) as usize;
+        let b_videos = b.count_videos(WhereVideo::Any) as usize;
+
+        // Combine counts for shared channels. The sort is stable, so ties keep
+        // `a`'s order
+        let b_counts = b_channels
+            .iter()
+            .map(|(count, channel)| (&channel.url, *count))
+            .collect::<HashMap<_, _>>();
+        // The maps can hold channels without watches, like one a video moved
+        // away from, so only count channels both histories watched
+        let a_counts = a_channels
+            .iter()
+            .map(|(count, channel)| (&channel.url, *count))
+            .collect::<HashMap<_, _>>();
+        let common_channels = a
+            .common_channels(b)
+            .iter()
+            .filter(|channel| {
+                a_counts.contains_key(&channel.url) && b_counts.contains_key(&channel.url)
+            })
+            .count();
+        let mut top_common_channels = a_channels
+            .iter()
+            .filter_map(|(a_count, channel)| {
+                let b_count = b_counts.get(&channel.url)?;
+                Some(ChannelCount {
+                    count: a_count + b_count,
+                    name: channel.name.clone(),
+                    url: channel.url.clone(),
+                    weekend_share: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        top_common_channels.sort_by_key(|channel| Reverse(channel.count));
+        top_common_channels.truncate(top);
+
+        let only = |channels: &[(usize, Rc<Channel>)], only: &[Rc<Channel>]| {
+            let only = only
+                .iter()
+                .map(|channel| &channel.url)
+                .collect::<HashSet<_>>();
+            channels
+                .iter()
+                .filter(|(_, channel)| only.contains(&channel.url))
+                .take(top)
+                .map(|(count, channel)| ChannelCount {
+                    count: *count,
+                    name: channel.name.clone(),
+                    url: channel.url.clone(),
+                    weekend_share: None,
+                })
+                .collect()
+        };
+
+        Comparison {
+            a_videos,
+            b_videos,
+            common_videos,
+            a_channels: a_channels.len(),
+            b_channels: b_channels.len(),
+            common_channels,
+            top_common_channels,
+            top_a_only_channels: only(&a_channels, &a_only),
+            top_b_only_channels: only(&b_channels, &b_only),
+            channel_jaccard: jaccard(common_channels, a_channels.len(), b_channels.len()),
+            video_jaccard: jaccard(common_videos, a_videos, b_videos),
+        }
+    }
+
+    pub fn print(&self, a_name: &str, b_name: &str) {
+        println!(
+            "{} {} {}",
+            "Videos in common:".dimmed(),
+            self.common_videos,
+            format!(
+                "({:.0}% of {}, {:.0}% of {})",
+                percent(self.common_videos, self.a_videos),
+                a_name,
+                percent(self.common_videos, self.b_videos),
+                b_name
+            )
+            .dimmed(),
+        );
+        println!(
+            "{} {} {}",
+            "Channels in common:".dimmed(),
+            self.common_channels,
+            format!(
+                "({:.0}% of {}, {:.0}% of {})",
+                percent(self.common_channels, self.a_channels),
+                a_name,
+                percent(self.common_channels, self.b_channels),
+                b_name
+            )
+            .dimmed(),
+        );
+
+        print_channels("Top shared channels", &self.top_common_channels);
+        print_channels(
+            &format!("Top channels only in {}", a_name),
+            &self.top_a_only_channels,
+        );
+        print_channels(
+            &format!("Top channels only in {}", b_name),
+            &self.top_b_only_channels,
+        );
+
+        println!();
+        println!(
+            "{} {:.3} {} {:.3}",
+            "Jaccard similarity: channels".bold(),
+            self.channel_jaccard,
+            "videos".bold(),
+            self.video_jaccard
+        );
+    }
+}
+
+/// Size of the intersection over size of the union, 0 when both sets are
+/// empty.
+fn jaccard(common: usize, a: usize, b: usize) -> f64 {
+    // `common` is never more than `a` or `b`, but don't underflow if it is
+    let union = (a + b).saturating_sub(common);
+    if union == 0 {
+        0.0
+    } else {
+        common as f64 / union as f64
+    }
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+fn print_channels(heading: &str, channels: &[ChannelCount]) {
+    println!();
+    println!("{}", heading.bold());
+    for (i, channel) in channels.iter().enumerate() {
+        println!(
+            "  {}. {} {}",
+            i + 1,
+            channel.name,
+            format!("({})", channel.count).dimmed()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jaccard() {
+        assert_eq!(jaccard(1, 2, 2), 1.0 / 3.0);
+        assert_eq!(jaccard(0, 0, 0), 0.0);
+        assert_eq!(jaccard(3, 1, 1), 0.0);
+    }
+
+    #[test]
+    fn test_unwatched_channels() {
+        let when = chrono::DateTime::parse_from_rfc3339("2023-01-01T10:00:00Z").unwrap();
+        let watches = [("v", "V", "Shared", "shared", when)];
+        let mut a = Models::from_watches(&watches);
+        let mut b = Models::from_watches(&watches);
+        for models in [&mut a, &mut b] {
+            // In both maps, but never watched
+            models.find_or_create_channel(&"orphan".into(), &"Orphan".into());
+        }
+
+        let comparison = Comparison::new(&a, &b, 10);
+
+        assert_eq!(comparison.a_channels, 1);
+        assert_eq!(comparison.common_channels, 1);
+        assert_eq!(comparison.channel_jaccard, 1.0);
+    }
+}
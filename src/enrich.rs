@@ -0,0 +1,444 @@
+//! Looks up upload dates and durations with the YouTube Data API, for the
+//! video age and watch time reports. Answers are cached next to the history
+//! cache, so each video is only asked for once. The requests need the
+//! `youtube-api` feature, everything else is always built so it can be tested.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Read when `--api-key` isn't passed.
+pub const API_KEY_VAR: &str = "YT_HISTORY_API_KEY";
+
+/// Video IDs per request, the most `videos.list` accepts.
+const BATCH_SIZE: usize = 50;
+
+/// Tries per batch before it's skipped, along with every batch after it.
+const ATTEMPTS: u32 = 4;
+
+/// The wait before the first retry, doubled before each one after.
+const FIRST_BACKOFF: Duration = Duration::from_secs(2);
+
+#[cfg(feature = "youtube-api")]
+const VIDEOS_URL: &str = "https://www.googleapis.com/youtube/v3/videos";
+
+/// What the API knows about each video, by video ID. `None` is a video the API
+/// didn't return, like a private or deleted one, so it isn't asked for again.
+type KnownVideos = HashMap<String, Option<Details>>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct Details {
+    uploaded: NaiveDate,
+    /// In seconds, `None` when the API's duration couldn't be read.
+    duration: Option<u64>,
+}
+
+/// What `look_up` found.
+pub struct Enrichment {
+    /// Upload dates by video ID, from the cache and the API.
+    pub upload_dates: HashMap<String, NaiveDate>,
+    /// Durations in seconds by video ID, from the cache and the API.
+    pub durations: HashMap<String, u64>,
+    /// Videos the API answered for this run.
+    pub requested: usize,
+    /// Why each skipped batch wasn't looked up. Its videos are asked for again
+    /// next run.
+    pub skipped: Vec<String>,
+}
+
+/// Why a request failed.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(not(feature = "youtube-api"), allow(dead_code))]
+enum FetchError {
+    /// Worth another try after a wait, like a quota or server error.
+    Retry(String),
+    Fatal(String),
+}
+
+#[derive(Deserialize)]
+struct VideoList {
+    #[serde(default)]
+    items: Vec<VideoItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoItem {
+    id: String,
+    snippet: Snippet,
+    content_details: Option<ContentDetails>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snippet {
+    published_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct ContentDetails {
+    /// An ISO 8601 duration, like `PT1H2M3S`.
+    duration: String,
+}
+
+/// Finds the upload date and duration of each of `ids`, asking the API for the
+/// ones the cache at `cache_path` doesn't have yet and adding them to it. Once
+/// a batch keeps failing the rest are skipped, rather than failing the report.
+pub fn look_up(
+    ids: &[&str],
+    api_key: &str,
+    cache_path: &Path,
+) -> Result<Enrichment, Box<dyn Error>> {
+    // A missing or unreadable cache is rebuilt
+    let mut known = read_cache(cache_path).unwrap_or_default();
+    let missing = ids
+        .iter()
+        .copied()
+        .filter(|id| !known.contains_key(*id))
+        .collect::<Vec<_>>();
+    let skipped = fill(
+        &mut known,
+        &missing,
+        |batch| request(batch, api_key),
+        std::thread::sleep,
+    );
+    if !missing.is_empty() {
+        write_cache(cache_path, &known)?;
+    }
+    let requested = missing.iter().filter(|id| known.contains_key(**id)).count();
+
+    let mut upload_dates = HashMap::new();
+    let mut durations = HashMap::new();
+    for (id, details) in known {
+        let Some(details) = details else { continue };
+        if let Some(duration) = details.duration {
+            durations.insert(id.clone(), duration);
+        }
+        upload_dates.insert(id, details.uploaded);
+    }
+
+    Ok(Enrichment {
+        upload_dates,
+        durations,
+        requested,
+        skipped,
+    })
+}
+
+/// Asks for `ids` in batches, adding what each answer says to `known`. Stops
+/// at the first batch that fails for good, since the ones after it would
+/// likely fail the same way, like when the quota has run out. Returns why each
+/// skipped batch wasn't looked up.
+fn fill(
+    known: &mut KnownVideos,
+    ids: &[&str],
+    mut request: impl FnMut(&[&str]) -> Result<String, FetchError>,
+    mut sleep: impl FnMut(Duration),
+) -> Vec<String> {
+    let mut skipped = Vec::new();
+
+    for (i, batch) in ids.chunks(BATCH_SIZE).enumerate() {
+        let found = with_backoff(|| request(batch), &mut sleep).and_then(|body| {
+            parse_response(&body).map_err(|error| format!("unexpected response: {}", error))
+        });
+        match found {
+            Ok(found) => {
+                for id in batch {
+                    known.insert(id.to_string(), found.get(*id).copied());
+                }
+            }
+            Err(error) => {
+                skipped.push(format!("{} videos: {}", batch.len(), error));
+
+                let rest = ids.len() - (i * BATCH_SIZE + batch.len());
+                if rest > 0 {
+                    skipped.push(format!("{} videos: not asked for after that", rest));
+                }
+                break;
+            }
+        }
+    }
+
+    skipped
+}
+
+/// Calls `request` until it succeeds, fails for good, or has been tried
+/// `ATTEMPTS` times, waiting twice as long before each retry.
+fn with_backoff(
+    mut request: impl FnMut() -> Result<String, FetchError>,
+    mut sleep: impl FnMut(Duration),
+) -> Result<String, String> {
+    let mut wait = FIRST_BACKOFF;
+
+    for attempt in 1..=ATTEMPTS {
+        match request() {
+            Ok(body) => return Ok(body),
+            Err(FetchError::Retry(error)) if attempt == ATTEMPTS => return Err(error),
+            Err(FetchError::Retry(_)) => {
+                sleep(wait);
+                wait *= 2;
+            }
+            Err(FetchError::Fatal(error)) => return Err(error),
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+/// Reads the upload dates and durations out of a `videos.list` response.
+fn parse_response(body: &str) -> serde_json::Result<HashMap<String, Details>> {
+    let list: VideoList = serde_json::from_str(body)?;
+
+    Ok(list
+        .items
+        .into_iter()
+        .map(|item| {
+            let details = Details {
+                uploaded: item.snippet.published_at.date_naive(),
+                duration: item
+                    .content_details
+                    .and_then(|content| parse_duration(&content.duration)),
+            };
+            (item.id, details)
+        })
+        .collect())
+}
+
+/// Reads an ISO 8601 duration like `PT1H2M3S` or `P1DT2H` into seconds. The
+/// API doesn't use years or months, so those aren't read.
+fn parse_duration(duration: &str) -> Option<u64> {
+    let mut seconds = 0;
+    let mut number = 0;
+    let mut digits = false;
+    let mut in_time = false;
+
+    for c in duration.strip_prefix('P')?.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            number = number * 10 + u64::from(digit);
+            digits = true;
+            continue;
+        }
+        let unit = match (c, in_time) {
+            ('T', false) if !digits => {
+                in_time = true;
+                continue;
+            }
+            ('W', false) => 7 * 24 * 60 * 60,
+            ('D', false) => 24 * 60 * 60,
+            ('H', true) => 60 * 60,
+            ('M', true) => 60,
+            ('S', true) => 1,
+            _ => return None,
+        };
+        if !digits {
+            return None;
+        }
+        seconds += number * unit;
+        number = 0;
+        digits = false;
+    }
+
+    (!digits).then_some(seconds)
+}
+
+#[cfg(feature = "youtube-api")]
+fn request(ids: &[&str], api_key: &str) -> Result<String, FetchError> {
+    let response = ureq::get(VIDEOS_URL)
+        .query("part", "snippet,contentDetails")
+        .query("id", &ids.join(","))
+        .query("key", api_key)
+        .call();
+
+    match response {
+        Ok(response) => response
+            .into_string()
+            .map_err(|error| FetchError::Retry(error.to_string())),
+        // Rate limits come back as 429. An exceeded daily quota is a 403,
+        // which waiting a few seconds won't fix
+        Err(ureq::Error::Status(code @ (429 | 500..=599), _)) => {
+            Err(FetchError::Retry(format!("HTTP {}", code)))
+        }
+        Err(ureq::Error::Status(code, _)) => Err(FetchError::Fatal(format!("HTTP {}", code))),
+        Err(error) => Err(FetchError::Retry(error.to_string())),
+    }
+}
+
+#[cfg(not(feature = "youtube-api"))]
+fn request(_ids: &[&str], _api_key: &str) -> Result<String, FetchError> {
+    Err(FetchError::Fatal("built without `youtube-api`".into()))
+}
+
+fn read_cache(path: &Path) -> Result<KnownVideos, Box<dyn Error>> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Writes to a temporary file first, so an interrupted run can't leave a
+/// truncated cache behind.
+fn write_cache(path: &Path, known: &KnownVideos) -> Result<(), Box<dyn Error>> {
+    let temp_path = path.with_extension("json.tmp");
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    serde_json::to_writer(&mut writer, known)?;
+    writer.into_inner()?.sync_all()?;
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(items: &[(&str, &str, &str)]) -> String {
+        let items = items
+            .iter()
+            .map(|(id, published, duration)| {
+                serde_json::json!({
+                    "id": id,
+                    "snippet": { "publishedAt": published },
+                    "contentDetails": { "duration": duration },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({ "kind": "youtube#videoListResponse", "items": items }).to_string()
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let body = response(&[
+            ("rtTWtzWav8I", "2023-06-01T17:00:12Z", "PT4M13S"),
+            ("dQw4w9WgXcQ", "2009-10-25T06:57:33Z", "PT?"),
+        ]);
+        let videos = parse_response(&body).unwrap();
+        assert_eq!(
+            videos["rtTWtzWav8I"],
+            Details {
+                uploaded: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+                duration: Some(253),
+            }
+        );
+        // A duration that can't be read doesn't lose the upload date
+        assert_eq!(videos["dQw4w9WgXcQ"].duration, None);
+
+        // No items at all when none of the videos exist
+        assert!(parse_response(r#"{"kind":"youtube#videoListResponse"}"#)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT15S"), Some(15));
+        assert_eq!(parse_duration("PT1H2M3S"), Some(3723));
+        assert_eq!(parse_duration("PT10M"), Some(600));
+        assert_eq!(parse_duration("P1DT2H"), Some(93600));
+        assert_eq!(parse_duration("P1W"), Some(604800));
+        // Upcoming live streams
+        assert_eq!(parse_duration("P0D"), Some(0));
+
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("PT5"), None);
+        assert_eq!(parse_duration("PTM"), None);
+        assert_eq!(parse_duration("P5H"), None);
+        assert_eq!(parse_duration("1H"), None);
+    }
+
+    #[test]
+    fn test_with_backoff() {
+        let mut waits = Vec::new();
+        let mut tries = 0;
+        let result = with_backoff(
+            || {
+                tries += 1;
+                if tries < 3 {
+                    Err(FetchError::Retry("HTTP 429".into()))
+                } else {
+                    Ok("{}".into())
+                }
+            },
+            |wait| waits.push(wait.as_secs()),
+        );
+        assert_eq!(result, Ok("{}".into()));
+        assert_eq!(waits, [2, 4]);
+
+        let mut tries = 0;
+        let result = with_backoff(
+            || {
+                tries += 1;
+                Err(FetchError::Retry("HTTP 403".into()))
+            },
+            |_| {},
+        );
+        assert_eq!(result, Err("HTTP 403".into()));
+        assert_eq!(tries, ATTEMPTS);
+
+        let mut tries = 0;
+        let result = with_backoff(
+            || {
+                tries += 1;
+                Err(FetchError::Fatal("HTTP 400".into()))
+            },
+            |_| {},
+        );
+        assert_eq!(result, Err("HTTP 400".into()));
+        assert_eq!(tries, 1);
+    }
+
+    #[test]
+    fn test_fill() {
+        let ids = (0..170).map(|i| format!("id{:03}", i)).collect::<Vec<_>>();
+        let ids = ids.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let mut batches = Vec::new();
+        let mut known = KnownVideos::new();
+        let skipped = fill(
+            &mut known,
+            &ids,
+            |batch| {
+                batches.push(batch.len());
+                match batch[0] {
+                    // The third batch keeps failing
+                    "id100" => Err(FetchError::Retry("HTTP 503".into())),
+                    // Only the first video of each batch still exists
+                    first => Ok(response(&[(first, "2020-01-02T03:04:05Z", "PT1M")])),
+                }
+            },
+            |_| {},
+        );
+
+        // The third batch is tried `ATTEMPTS` times, then nothing after it
+        assert_eq!(batches, [50, 50, 50, 50, 50, 50]);
+        assert_eq!(
+            skipped,
+            ["50 videos: HTTP 503", "20 videos: not asked for after that"]
+        );
+        // The skipped batches are asked for again next time
+        assert_eq!(known.len(), 100);
+        assert!(!known.contains_key("id100"));
+        assert!(!known.contains_key("id150"));
+        assert_eq!(
+            known["id050"],
+            Some(Details {
+                uploaded: NaiveDate::from_ymd_opt(2020, 1, 2).unwrap(),
+                duration: Some(60),
+            })
+        );
+        assert_eq!(known["id051"], None);
+
+        // A failing last batch leaves nothing else to skip
+        let skipped = fill(
+            &mut KnownVideos::new(),
+            &ids[..50],
+            |_| Err(FetchError::Fatal("HTTP 403".into())),
+            |_| {},
+        );
+        assert_eq!(skipped, ["50 videos: HTTP 403"]);
+    }
+}
@@ -0,0 +1,139 @@
+mod csv;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+use std::error::Error;
+use std::rc::Rc;
+
+use chrono::{DateTime, Duration, DurationRound, FixedOffset, NaiveDate};
+
+use crate::model::{Channel, Video, Watched};
+
+pub use csv::Column;
+
+/// Options shared by all exports.
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    /// Write video and channel IDs instead of URLs where they can be
+    /// extracted. Only applies to CSV, Parquet always has both.
+    pub ids_only: bool,
+    /// Round timestamps, so exports can be shared without precise times.
+    pub round_time: Option<TimeRounding>,
+    /// CSV columns, in order. Empty for `Column::DEFAULT`.
+    pub columns: Vec<Column>,
+}
+
+impl ExportOptions {
+    /// The timestamp to export for `watched`.
+    pub fn when(&self, watched: &Watched) -> DateTime<FixedOffset> {
+        match self.round_time {
+            Some(rounding) => rounding.round(watched.when),
+            None => watched.when,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TimeRounding {
+    Hour,
+    Day,
+}
+
+impl TimeRounding {
+    /// Rounds to the nearest hour or day. Days start at midnight in `when`'s
+    /// own offset, not UTC.
+    pub fn round(&self, when: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        let delta = match self {
+            TimeRounding::Hour => Duration::hours(1),
+            TimeRounding::Day => Duration::days(1),
+        };
+
+        // Only fails for dates near the limits of `DateTime`
+        when.duration_round(delta).unwrap_or(when)
+    }
+}
+
+impl std::str::FromStr for TimeRounding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(TimeRounding::Hour),
+            "day" => Ok(TimeRounding::Day),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Writes one row per watch to a CSV file at `path`, in the order given.
+pub fn write_csv(
+    watches: &[&Watched],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    csv::write(watches, path, options)
+}
+
+/// Writes one row per channel to a CSV file at `path`, with the date of its
+/// first watch, in the order given.
+pub fn write_channel_timeline_csv(
+    discoveries: &[(NaiveDate, Rc<Channel>)],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    csv::write_channel_timeline(discoveries, path, options)
+}
+
+/// Writes one row per video to a CSV file at `path`, with its watch count, in
+/// the order given.
+pub fn write_video_list_csv(
+    videos: &[(usize, Rc<Video>)],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    csv::write_video_list(videos, path, options)
+}
+
+/// Writes the number of watches on each day to a CSV file at `path`, in the
+/// order given.
+pub fn write_timeline_csv(days: &[(NaiveDate, usize)], path: &str) -> Result<(), Box<dyn Error>> {
+    csv::write_timeline(days, path)
+}
+
+/// Writes one row per watch to a Parquet file at `path`, in the order given.
+#[cfg(feature = "parquet")]
+pub fn write_parquet(
+    watches: &[&Watched],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    parquet::write(watches, path, options)
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_parquet(
+    _watches: &[&Watched],
+    _path: &str,
+    _options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    Err("Parquet export is not available, rebuild with `--features parquet`".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_time() {
+        let when = DateTime::parse_from_rfc3339("2023-06-04T21:31:00-05:00").unwrap();
+
+        assert_eq!(
+            TimeRounding::Hour.round(when).to_rfc3339(),
+            "2023-06-04T22:00:00-05:00"
+        );
+        assert_eq!(
+            TimeRounding::Day.round(when).to_rfc3339(),
+            "2023-06-05T00:00:00-05:00"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use std::rc::Rc;
+
+use chrono::NaiveDate;
+
+use super::ExportOptions;
+use crate::model::{Channel, Video, Watched};
+
+/// A CSV column, chosen with `--columns`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Column {
+    Timestamp,
+    /// The video URL, or with `--ids-only` its ID.
+    Video,
+    VideoId,
+    VideoUrl,
+    VideoTitle,
+    /// The channel URL, or with `--ids-only` its ID.
+    Channel,
+    ChannelId,
+    ChannelUrl,
+    ChannelName,
+}
+
+impl Column {
+    pub const DEFAULT: [Column; 5] = [
+        Column::Timestamp,
+        Column::Video,
+        Column::VideoTitle,
+        Column::Channel,
+        Column::ChannelName,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Timestamp => "timestamp",
+            Column::Video => "video",
+            Column::VideoId => "video_id",
+            Column::VideoUrl => "video_url",
+            Column::VideoTitle => "video_title",
+            Column::Channel => "channel",
+            Column::ChannelId => "channel_id",
+            Column::ChannelUrl => "channel_url",
+            Column::ChannelName => "channel_name",
+        }
+    }
+}
+
+impl std::str::FromStr for Column {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timestamp" | "watched_at" => Ok(Column::Timestamp),
+            "video" => Ok(Column::Video),
+            "video_id" => Ok(Column::VideoId),
+            "video_url" => Ok(Column::VideoUrl),
+            "video_title" => Ok(Column::VideoTitle),
+            "channel" => Ok(Column::Channel),
+            "channel_id" => Ok(Column::ChannelId),
+            "channel_url" => Ok(Column::ChannelUrl),
+            "channel_name" => Ok(Column::ChannelName),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn write(
+    watches: &[&Watched],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let columns = if options.columns.is_empty() {
+        &Column::DEFAULT[..]
+    } else {
+        &options.columns[..]
+    };
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let header = columns.iter().map(Column::name).collect::<Vec<_>>();
+    write_row(&mut writer, &header)?;
+
+    for watched in watches {
+        let video = &watched.video;
+        let channel = &video.channel;
+        let timestamp = options.when(watched).to_rfc3339();
+
+        let row = columns
+            .iter()
+            .map(|column| match column {
+                Column::Timestamp => timestamp.as_str(),
+                Column::Video if options.ids_only => video.id_or_url(),
+                Column::Video | Column::VideoUrl => &video.url,
+                Column::VideoId => video.video_id().unwrap_or(""),
+                Column::VideoTitle => &video.title,
+                Column::Channel if options.ids_only => channel.id_or_url(),
+                Column::Channel | Column::ChannelUrl => &channel.url,
+                Column::ChannelId => channel.channel_id().unwrap_or(""),
+                Column::ChannelName => &channel.name,
+            })
+            .collect::<Vec<_>>();
+        write_row(&mut writer, &row)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_channel_timeline(
+    discoveries: &[(NaiveDate, Rc<Channel>)],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_row(&mut writer, &["first_watched", "channel", "channel_name"])?;
+
+    for (date, channel) in discoveries {
+        let id = if options.ids_only {
+            channel.id_or_url()
+        } else {
+            &channel.url
+        };
+        write_row(&mut writer, &[&date.to_string(), id, &channel.name])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_video_list(
+    videos: &[(usize, Rc<Video>)],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_row(
+        &mut writer,
+        &["watches", "video", "video_title", "channel", "channel_name"],
+    )?;
+
+    for (count, video) in videos {
+        let (video_id, channel_id) = if options.ids_only {
+            (video.id_or_url(), video.channel.id_or_url())
+        } else {
+            (video.url.as_str(), video.channel.url.as_str())
+        };
+        write_row(
+            &mut writer,
+            &[
+                &count.to_string(),
+                video_id,
+                &video.title,
+                channel_id,
+                &video.channel.name,
+            ],
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_timeline(days: &[(NaiveDate, usize)], path: &str) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_row(&mut writer, &["date", "watches"])?;
+
+    for (date, count) in days {
+        write_row(&mut writer, &[&date.to_string(), &count.to_string()])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_row<W: Write>(writer: &mut W, fields: &[&str]) -> std::io::Result<()> {
+    let fields = fields.iter().map(|field| escape(field)).collect::<Vec<_>>();
+    writeln!(writer, "{}", fields.join(","))
+}
+
+/// Quotes a field if it contains a delimiter, quote or newline (RFC 4180).
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("a, b"), "\"a, b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,135 @@
+use std::error::Error;
+use std::fs::File;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+
+use super::ExportOptions;
+use crate::model::Watched;
+
+// Arrow stores one timezone per column, so watches are normalized to UTC
+// instants rather than keeping each row's original offset.
+const TIMEZONE: &str = "UTC";
+
+pub fn write(
+    watches: &[&Watched],
+    path: &str,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let timestamps = TimestampMillisecondArray::from(
+        watches
+            .iter()
+            .map(|watched| options.when(watched).timestamp_millis())
+            .collect::<Vec<_>>(),
+    )
+    .with_timezone(TIMEZONE);
+    let video_ids = StringArray::from_iter(watches.iter().map(|w| w.video.video_id()));
+    let video_titles = StringArray::from_iter_values(watches.iter().map(|w| &w.video.title));
+    let channel_ids = StringArray::from_iter(watches.iter().map(|w| w.video.channel.channel_id()));
+    let channel_names =
+        StringArray::from_iter_values(watches.iter().map(|w| &w.video.channel.name));
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some(TIMEZONE.into())),
+            false,
+        ),
+        Field::new("video_id", DataType::Utf8, true),
+        Field::new("video_title", DataType::Utf8, false),
+        Field::new("channel_id", DataType::Utf8, true),
+        Field::new("channel_name", DataType::Utf8, false),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamps),
+        Arc::new(video_ids),
+        Arc::new(video_titles),
+        Arc::new(channel_ids),
+        Arc::new(channel_names),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::model::Models;
+
+    #[test]
+    fn test_round_trip() {
+        let when = chrono::DateTime::parse_from_rfc3339("2023-06-04T21:31:00-05:00").unwrap();
+        let models = Models::from_watches(&[
+            (
+                "https://www.youtube.com/watch?v=rtTWtzWav8I",
+                "First",
+                "Channel",
+                "https://www.youtube.com/channel/UCchannel",
+                when,
+            ),
+            // No IDs to extract from these URLs
+            ("video", "Second", "Other", "other", when),
+        ]);
+        let watches = models.watches().iter().collect::<Vec<_>>();
+        let path =
+            std::env::temp_dir().join(format!("yt-history-test-{}.parquet", std::process::id()));
+
+        write(&watches, path.to_str().unwrap(), &ExportOptions::default()).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 2);
+        let batch = &batches[0];
+        let types = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                (
+                    "timestamp".to_string(),
+                    DataType::Timestamp(TimeUnit::Millisecond, Some(TIMEZONE.into()))
+                ),
+                ("video_id".to_string(), DataType::Utf8),
+                ("video_title".to_string(), DataType::Utf8),
+                ("channel_id".to_string(), DataType::Utf8),
+                ("channel_name".to_string(), DataType::Utf8),
+            ]
+        );
+
+        let timestamps = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(timestamps.value(0), when.timestamp_millis());
+        let video_ids = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(video_ids.value(0), "rtTWtzWav8I");
+        assert!(video_ids.is_null(1));
+    }
+}
@@ -1,181 +1,1042 @@
+mod compare;
+mod enrich;
+mod export;
 mod model;
+mod options;
 mod parser;
+mod report;
 mod utf8_reader;
+mod yaml;
 
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, IsTerminal};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use colored::Colorize;
+use serde::Serialize;
 
-use crate::model::{Models, WhereVideo, WhereWatched};
-use crate::parser::ParseError;
+use crate::compare::Comparison;
+use crate::model::{
+    ChannelConflictPolicy, ChannelMatcher, Models, MonthDay, Video, WhereVideo, WhereWatched,
+};
+use crate::options::{ExportOrder, Format, Options, ShortsFilter};
+use crate::parser::{ParseError, ParseOptions, ParseStats};
+use crate::report::{Report, ReportOptions, Summary, VideoCount};
 
 const COMMAND_NAME: &str = "yt-history";
 const USE_CACHE: bool = true;
-const DEFAULT_DATA_PATH: &str = "data/watch-history.html";
+/// Characters of input shown on each side of a parse error with `--explain`.
+const EXPLAIN_CONTEXT: usize = 100;
+/// How often, in rows, parsing progress is printed.
+const PROGRESS_EVERY: usize = 100_000;
+/// How often, in rows, the live count is redrawn when stderr is a terminal.
+const LIVE_PROGRESS_EVERY: usize = 1_000;
+/// How many `--skip-errors` messages are printed before just the count.
+const SKIPPED_SHOWN: usize = 10;
+/// How far past now a watch can be before it counts as in the future.
+const FUTURE_GRACE_HOURS: i64 = 24;
+
+// Exit codes, so scripts can tell failures apart. 0 is success, and 1 is any
+// other error. Listed in the README.
+/// The command line couldn't be parsed.
+const EXIT_USAGE: i32 = 2;
+/// A file couldn't be read or written.
+const EXIT_IO: i32 = 3;
+/// The history couldn't be parsed.
+const EXIT_PARSE: i32 = 4;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 fn main() -> Result<()> {
-    let args = env::args().skip(1).collect::<Vec<_>>();
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{} {}", "Error:".red(), error.to_string().bold());
+            print_usage();
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    if options.cache_info {
+        print_cache_info(&options);
+        return Ok(());
+    }
+
+    if let Some(other_path) = &options.compare {
+        return compare(&options, other_path);
+    }
+
+    let mut models = load_or_exit(&options.data_path, &options);
+    // Structured output and exports are still written, just empty
+    let exports = options.csv.is_some()
+        || options.channel_timeline.is_some()
+        || options.timeline_csv.is_some()
+        || options.parquet.is_some();
+    if models.is_empty() && options.format == Format::Console && !exports {
+        eprintln!("{} {}", "No watches found in".yellow(), options.data_path);
+        return Ok(());
+    }
+    if options.prune {
+        prune(&mut models, &options)?;
+    }
+    apply_filters(&mut models, &options);
+
+    if options.count_only {
+        let unique_videos = models.count_videos(WhereVideo::Any);
+        let watches = models.count_watches(WhereWatched::Any);
+        match options.format {
+            Format::Console => report::print_totals(unique_videos, watches),
+            format => print_structured(
+                &serde_json::json!({ "unique_videos": unique_videos, "watches": watches }),
+                format,
+            )?,
+        }
+        return Ok(());
+    }
+
+    if let Some(url) = &options.watch_count_of {
+        print_watches_of(&models, url);
+        return Ok(());
+    }
+
+    if let Some(day) = options.on_day {
+        print_watches_on_day(&models, day);
+        return Ok(());
+    }
+
+    if options.find_dupes {
+        print_duplicate_titles(&models);
+        return Ok(());
+    }
+
+    if options.list_channels {
+        print_channel_list(&models);
+        return Ok(());
+    }
+
+    if options.list_videos {
+        let videos = models.top_videos(usize::MAX);
+        match &options.csv {
+            Some(path) => {
+                let export_options = options.export_options();
+                if let Err(error) = export::write_video_list_csv(&videos, path, &export_options) {
+                    println!("{} {}", "Error:".red(), error);
+                    std::process::exit(EXIT_IO);
+                }
+                eprintln!("{} {}", "Wrote video list to".dimmed(), path.white());
+            }
+            None => print_video_list(&videos, options.format)?,
+        }
+        return Ok(());
+    }
+
+    if options.first_watch_of.is_some() || options.last_watch_of.is_some() {
+        if let Some(channel) = &options.first_watch_of {
+            print_channel_watch(&models, channel, true);
+        }
+        if let Some(channel) = &options.last_watch_of {
+            print_channel_watch(&models, channel, false);
+        }
+        return Ok(());
+    }
+
+    let export_watches = || match options.sort {
+        ExportOrder::Chronological => models.watches_sorted(),
+        ExportOrder::File => models.watches().iter().collect(),
+    };
+
+    let export_options = options.export_options();
+
+    if let Some(path) = &options.csv {
+        if let Err(error) = export::write_csv(&export_watches(), path, &export_options) {
+            println!("{} {}", "Error:".red(), error);
+            std::process::exit(EXIT_IO);
+        }
+        eprintln!("{} {}", "Wrote CSV export to".dimmed(), path.white());
+    }
+
+    if let Some(path) = &options.channel_timeline {
+        let discoveries = models.channel_discovery_dates();
+        if let Err(error) = export::write_channel_timeline_csv(&discoveries, path, &export_options)
+        {
+            println!("{} {}", "Error:".red(), error);
+            std::process::exit(EXIT_IO);
+        }
+        eprintln!("{} {}", "Wrote channel timeline to".dimmed(), path.white());
+    }
+
+    if let Some(path) = &options.timeline_csv {
+        if let Err(error) = export::write_timeline_csv(&models.watches_per_day(), path) {
+            println!("{} {}", "Error:".red(), error);
+            std::process::exit(EXIT_IO);
+        }
+        eprintln!("{} {}", "Wrote daily timeline to".dimmed(), path.white());
+    }
+
+    if let Some(path) = &options.parquet {
+        if let Err(error) = export::write_parquet(&export_watches(), path, &export_options) {
+            println!("{} {}", "Error:".red(), error);
+            std::process::exit(EXIT_IO);
+        }
+        eprintln!("{} {}", "Wrote Parquet export to".dimmed(), path.white());
+    }
+
+    let mut report_options = options.report_options();
+    if options.summary_only {
+        let summary = Summary::new(&models, &report_options);
+        match options.format {
+            Format::Console => summary.print(),
+            format => print_structured(&summary, format)?,
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &options.upload_dates {
+        match load_upload_dates(path) {
+            Ok(upload_dates) => report_options.upload_dates = Some(upload_dates),
+            Err(error) => {
+                println!("{} {}", "Error:".red(), error);
+                std::process::exit(load_exit_code(error.as_ref()));
+            }
+        }
+    }
+
+    if options.enrich {
+        enrich_videos(&models, &options, &mut report_options);
+    }
+
+    if !options.periods.is_empty() {
+        return print_periods(&models, &options, &report_options);
+    }
+
+    let start = Instant::now();
+    let mut report = Report::new(&models, &report_options);
+    if options.ids_only {
+        report.use_ids();
+    }
+
+    match options.format {
+        Format::Console => report.print(),
+        format => print_structured(&report, format)?,
+    }
+
+    if options.profile {
+        print_profile("report", start.elapsed());
+    }
+
+    Ok(())
+}
+
+/// Reads a JSON object of upload dates (`YYYY-MM-DD`) by video ID or URL.
+fn load_upload_dates(path: &str) -> Result<HashMap<String, chrono::NaiveDate>> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+/// Adds upload dates and durations from the YouTube Data API to the report,
+/// printing the error and exiting if that can't be done at all.
+fn enrich_videos(models: &Models, options: &Options, report_options: &mut ReportOptions) {
+    let Some(api_key) = (options.api_key.clone()).or_else(|| env::var(enrich::API_KEY_VAR).ok())
+    else {
+        println!(
+            "{} --enrich needs an API key, pass --api-key or set {}",
+            "Error:".red(),
+            enrich::API_KEY_VAR
+        );
+        std::process::exit(EXIT_USAGE);
+    };
+
+    // Durations only come from the API, so every video is looked up
+    let videos = models.all_videos_sorted();
+    let ids = videos
+        .iter()
+        .filter_map(|video| video.video_id())
+        .collect::<Vec<_>>();
+
+    let cache_path = cache_path(&options.data_path, ".videos");
+    let enrichment = match enrich::look_up(&ids, &api_key, &cache_path) {
+        Ok(enrichment) => enrichment,
+        Err(error) => {
+            println!("{} {}", "Error:".red(), error);
+            std::process::exit(load_exit_code(error.as_ref()));
+        }
+    };
 
-    if args.len() > 1 {
-        println!("{} {}", "Error:".red(), "Too many arguments".bold());
-        print_usage();
-        std::process::exit(1);
+    if enrichment.requested > 0 {
+        eprintln!(
+            "{} {} {}",
+            "Looked up".dimmed(),
+            enrichment.requested,
+            "videos".dimmed()
+        );
+    }
+    for reason in &enrichment.skipped {
+        eprintln!("{} {}", "Skipped".yellow(), reason);
     }
 
-    let data_path: &str = args.get(0).map(|s| s.as_str()).unwrap_or(DEFAULT_DATA_PATH);
+    // Dates from --upload-dates win
+    let upload_dates = report_options.upload_dates.get_or_insert_with(HashMap::new);
+    for (id, date) in enrichment.upload_dates {
+        upload_dates.entry(id).or_insert(date);
+    }
+    report_options.durations = Some(enrichment.durations);
+}
 
-    let models = match load_models(data_path) {
+/// Loads a history, printing the error and exiting if that fails.
+fn load_or_exit(data_path: &str, options: &Options) -> Models {
+    let result = if options.freeze_range {
+        load_frozen_range(data_path, options)
+    } else {
+        load_models(data_path, &options.parse_options())
+    };
+
+    match result {
         Ok(models) => models,
         Err(error) => {
             // ParseError is logged in parse(), only log other errors
-            if error.downcast_ref::<ParseError>().is_none() {
+            if let Some(error) = error.downcast_ref::<ParseError>() {
+                if options.explain {
+                    explain_parse_error(data_path, error);
+                }
+            } else {
                 println!("{} {}", "Error:".red(), error);
+
+                let mut source = error.source();
+                while let Some(cause) = source {
+                    println!("  {} {}", "Caused by:".dimmed(), cause);
+                    source = cause.source();
+                }
             }
 
-            std::process::exit(1);
+            std::process::exit(load_exit_code(error.as_ref()));
         }
-    };
-
-    println!(
-        "{} {} {} {} {}",
-        "History contains".dimmed(),
-        models.count_videos(WhereVideo::Any),
-        "unique videos and".dimmed(),
-        models.count_watches(WhereWatched::Any),
-        "watches".dimmed(),
-    );
+    }
+}
 
-    let video_watches = models.count_watched_by_video();
-    let mut video_watch_counts = video_watches.iter().collect::<Vec<_>>();
-    video_watch_counts.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
+/// `EXIT_IO` if reading the file failed anywhere in `error`'s chain,
+/// otherwise `EXIT_PARSE`.
+fn load_exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error.is::<std::io::Error>() {
+            return EXIT_IO;
+        }
+        source = error.source();
+    }
 
-    const COUNT: usize = 50;
+    EXIT_PARSE
+}
 
-    println!();
-    println!(
+/// Drops videos and channels without watches, and rewrites the cache without
+/// them. Parsing never leaves any, but a cache from elsewhere might.
+fn prune(models: &mut Models, options: &Options) -> Result<()> {
+    let removed = models.prune_orphans();
+    eprintln!(
         "{} {} {}",
-        "Top".bold(),
-        format!("{}", COUNT).bold(),
-        "most watched videos".bold()
+        "Pruned".dimmed(),
+        removed,
+        "videos and channels without watches".dimmed(),
     );
-    for (i, (_, (count, video))) in video_watch_counts.iter().enumerate().take(COUNT) {
-        let s = if *count != 1 { "s" } else { "" };
 
+    if let Some(cache_path) = active_cache_path(options).filter(|_| removed > 0) {
+        if write_cache(&cache_path, models)? {
+            eprintln!(
+                "{} {}",
+                "Rewrote cache at".dimmed(),
+                cache_path.to_str().unwrap().white()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the checks and filters selected by `options`, printing what each
+/// one did.
+fn apply_filters(models: &mut Models, options: &Options) {
+    if options.utc {
+        // First, so --since and --until are UTC dates too
+        models.use_utc();
+    }
+
+    if options.profile || options.validate {
+        eprintln!(
+            "{} {}",
+            "Watches out of chronological order:".dimmed(),
+            models.count_out_of_order_watches()
+        );
+    }
+
+    // Allow for a clock that's a little behind the one that wrote the history
+    let future = chrono::Utc::now() + chrono::Duration::hours(FUTURE_GRACE_HOURS);
+    if options.validate {
+        eprintln!(
+            "{} {}",
+            "Watches in the future:".dimmed(),
+            models.count_future_watches(future)
+        );
+    }
+
+    if options.drop_future {
+        let removed = models.remove_future_watches(future);
+        eprintln!(
+            "{} {} {}",
+            "Removed".dimmed(),
+            removed,
+            "watches in the future with --drop-future".dimmed(),
+        );
+    }
+
+    if options.since.is_some() || options.until.is_some() {
+        let removed = models.remove_watches_outside(options.since, options.until);
+        eprintln!(
+            "{} {} {}",
+            "Removed".dimmed(),
+            removed,
+            "watches outside --since/--until".dimmed(),
+        );
+    }
+
+    if options.no_music {
+        let removed = models.remove_watches_matching(|w| w.video.is_music());
+        eprintln!(
+            "{} {} {}",
+            "Removed".dimmed(),
+            removed,
+            "YouTube Music watches with --no-music".dimmed(),
+        );
+    }
+
+    for name in &options.exclude_channels {
+        let removed = models.remove_watches_of_channel(ChannelMatcher::name_ignoring_case(name));
+        eprintln!(
+            "{} {} {} {}",
+            "Removed".dimmed(),
+            removed,
+            "watches of".dimmed(),
+            name,
+        );
+    }
+
+    let removed_shorts = match options.shorts {
+        ShortsFilter::Include => 0,
+        ShortsFilter::Exclude => models.remove_watches_matching(|w| w.video.is_short()),
+        ShortsFilter::Only => models.remove_watches_matching(|w| !w.video.is_short()),
+    };
+    if options.shorts != ShortsFilter::Include {
+        eprintln!(
+            "{} {} {}",
+            "Removed".dimmed(),
+            removed_shorts,
+            "watches with --exclude-shorts/--only-shorts".dimmed(),
+        );
+    }
+
+    if options.group_channels_by_handle {
+        let merged = models.group_channels_by_handle();
+        eprintln!(
+            "{} {} {}",
+            "Merged".dimmed(),
+            merged,
+            "channels with --group-channels-by-handle".dimmed(),
+        );
+    }
+
+    if let Some(window) = options.collapse_window {
+        let collapsed = models.collapse_repeats(window);
+        eprintln!(
+            "{} {} {} {} {}",
+            "Collapsed".dimmed(),
+            collapsed,
+            "repeat watches within".dimmed(),
+            window.num_minutes(),
+            "minutes".dimmed(),
+        );
+    }
+
+    if let Some(n) = options.sample {
+        // Without a seed, pick one and print it so the sample can be repeated
+        let seed = options.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos() as u64)
+        });
+        let removed = models.sample(n, seed);
+        eprintln!(
+            "{} {} {} {}{}",
+            "Sampled".yellow(),
+            models.watches().len(),
+            "watches with seed".yellow(),
+            seed,
+            ", counts are estimates".yellow(),
+        );
+        if removed == 0 {
+            eprintln!(
+                "{}",
+                "The history has no more watches than --sample".dimmed()
+            );
+        }
+    }
+}
+
+/// Prints how many times the video at `url` was watched, and when.
+fn print_watches_of(models: &Models, url: &str) {
+    let watches = models.watches_of(url);
+
+    match watches.first() {
+        Some(watched) => println!(
+            "{} {} {} time{}",
+            watched.video.title.bold(),
+            "watched".dimmed(),
+            watches.len(),
+            if watches.len() != 1 { "s" } else { "" }
+        ),
+        None => println!("{} {}", "Never watched".dimmed(), url),
+    }
+
+    for watched in watches {
+        println!("  {}", watched.when.format("%Y-%m-%d %H:%M %:z"));
+    }
+}
+
+/// Prints the videos watched on `day` in each year, oldest first.
+fn print_watches_on_day(models: &Models, day: MonthDay) {
+    let years = models.watches_on_day(day);
+    if years.is_empty() {
+        println!(
+            "{} {:02}-{:02}",
+            "No watches on".dimmed(),
+            day.month,
+            day.day
+        );
+    }
+
+    for (year, watches) in years {
         println!(
-            "  {index}. {title} {viewed} {count} {time}{s}",
-            index = i + 1,
-            title = video.title,
-            viewed = "viewed".dimmed(),
-            count = count,
-            time = "time".dimmed(),
-            s = s.dimmed(),
+            "{} {}",
+            year.to_string().bold(),
+            format!(
+                "({} watch{})",
+                watches.len(),
+                if watches.len() != 1 { "es" } else { "" }
+            )
+            .dimmed()
         );
+        for watched in watches {
+            println!(
+                "  {} {} {}",
+                watched.when.format("%H:%M").to_string().dimmed(),
+                watched.video.title,
+                format!("by {}", watched.video.channel.name).dimmed()
+            );
+        }
     }
+}
 
-    let channel_watches = models.count_watched_by_channel();
-    let mut channel_watch_counts = channel_watches.iter().collect::<Vec<_>>();
-    channel_watch_counts.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
+/// Prints the top lists for each `--period`. Periods are filtered from the
+/// whole history independently, so they can overlap.
+fn print_periods(models: &Models, options: &Options, report_options: &ReportOptions) -> Result<()> {
+    let mut reports = Vec::new();
+    for period in &options.periods {
+        let mut models = models.clone();
+        models.remove_watches_outside(period.since, period.until);
+        let mut report = Report::new(&models, report_options);
+        if options.ids_only {
+            report.use_ids();
+        }
 
-    println!();
+        match options.format {
+            Format::Console => {
+                let date = |date: Option<chrono::NaiveDate>, open: &str| {
+                    date.map_or(open.to_string(), |date| date.to_string())
+                };
+                println!();
+                println!(
+                    "{} {}",
+                    period.name.bold().underline(),
+                    format!(
+                        "({} to {})",
+                        date(period.since, "start"),
+                        date(period.until, "end")
+                    )
+                    .dimmed()
+                );
+                report::print_totals(report.summary.unique_videos, report.summary.watches);
+                report.print_top_lists();
+            }
+            _ => reports.push(serde_json::json!({
+                "name": period.name,
+                "since": period.since,
+                "until": period.until,
+                "report": report,
+            })),
+        }
+    }
+
+    if options.format != Format::Console {
+        print_structured_list("periods", &reports, options.format)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the videos that share a title with another video, and how often each
+/// was watched.
+fn print_duplicate_titles(models: &Models) {
+    let groups = models.duplicate_titles();
     println!(
-        "{} {} {}",
-        "Top".bold(),
-        format!("{}", COUNT).bold(),
-        "most watched channels".bold()
+        "{} {}",
+        "Titles shared by different URLs:".dimmed(),
+        groups.len()
     );
-    for (i, (_, (count, channel))) in channel_watch_counts.iter().enumerate().take(COUNT) {
-        let s = if *count != 1 { "s" } else { "" };
 
-        println!(
-            "  {index}. {title} {viewed} {count} {time}{s}",
-            index = i + 1,
-            title = channel.name,
-            viewed = "viewed".dimmed(),
-            count = count,
-            time = "time".dimmed(),
-            s = s.dimmed(),
-        );
+    let counts = models.count_watched_by_video();
+    for videos in groups {
+        println!("{}", videos[0].title.bold());
+        for video in videos {
+            let count = counts.get(&video.url).map_or(0, |(count, _)| *count);
+            println!(
+                "  {} {}",
+                video.url,
+                format!(
+                    "by {}, watched {} time{}",
+                    video.channel.name,
+                    count,
+                    if count != 1 { "s" } else { "" }
+                )
+                .dimmed()
+            );
+        }
     }
+}
 
-    println!();
-    println!("{}", "Top channel views by year".bold());
+/// Prints every channel as `count\tname\turl`, most watched first.
+fn print_channel_list(models: &Models) {
+    for (count, channel) in models.top_channels(usize::MAX) {
+        println!("{}\t{}\t{}", count, channel.name, channel.url);
+    }
+}
+
+/// Prints every video in `videos` as `count\ttitle\tchannel\turl`, or as a
+/// list in a structured format.
+fn print_video_list(videos: &[(usize, Rc<Video>)], format: Format) -> Result<()> {
+    match format {
+        Format::Console => {
+            for (count, video) in videos {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    count, video.title, video.channel.name, video.url
+                );
+            }
+        }
+        format => {
+            let videos = videos
+                .iter()
+                .map(|(count, video)| VideoCount {
+                    count: *count,
+                    title: video.title.clone(),
+                    url: video.url.clone(),
+                    channel_name: video.channel.name.clone(),
+                    channel_url: video.channel.url.clone(),
+                })
+                .collect::<Vec<_>>();
+            print_structured_list("videos", &videos, format)?;
+        }
+    }
 
-    let channel_watches_by_year = models.count_watched_by_channel_by_year();
-    let mut channel_watches_by_year = channel_watches_by_year.iter().collect::<Vec<_>>();
-    channel_watches_by_year.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(())
+}
+
+/// Prints `value` as JSON, TOML or YAML. The console format is each caller's
+/// own, so it's an error here.
+fn print_structured<T: Serialize>(value: &T, format: Format) -> Result<()> {
+    let text = match format {
+        Format::Console => return Err("console output can't be serialized".into()),
+        Format::Json => serde_json::to_string_pretty(value)?,
+        Format::Toml => toml::to_string_pretty(value)?,
+        Format::Yaml => yaml::to_string(value)?,
+    };
+    println!("{}", text.trim_end());
+
+    Ok(())
+}
 
-    for (year, channel_watches) in channel_watches_by_year.iter_mut() {
-        let mut channel_watches = channel_watches.iter().collect::<Vec<_>>();
-        channel_watches.sort_by(|a, b| b.1 .0.cmp(&a.1 .0));
+/// Prints `list` like `print_structured`. TOML documents are tables, so there
+/// the list goes under `key`.
+fn print_structured_list<T: Serialize>(key: &str, list: &[T], format: Format) -> Result<()> {
+    match format {
+        Format::Toml => print_structured(&HashMap::from([(key, list)]), format),
+        format => print_structured(&list, format),
+    }
+}
 
-        print!("{}: ", year);
+/// Prints the first (or last) watch from `channel`.
+fn print_channel_watch(models: &Models, channel: &str, first: bool) {
+    let watches = models.watches_of_channel(channel);
+    let (label, watched) = if first {
+        ("First", watches.first())
+    } else {
+        ("Last", watches.last())
+    };
 
-        for (_, (count, channel)) in channel_watches.iter().take(10) {
-            print!("{} {} ", channel.name, format!("({})", count).dimmed());
+    match watched {
+        Some(watched) => {
+            println!(
+                "{} {} {}",
+                format!("{} watch from", label).dimmed(),
+                watched.video.channel.name.bold(),
+                format!("({} watches in total)", watches.len()).dimmed()
+            );
+            println!("  {}", watched.video.title);
+            println!("  {}", watched.video.url);
+            println!("  {}", watched.when.format("%Y-%m-%d %H:%M %:z"));
         }
+        None => println!(
+            "{} {}",
+            "No watches from a channel matching".yellow(),
+            channel
+        ),
+    }
+}
+
+fn compare(options: &Options, other_path: &str) -> Result<()> {
+    let mut a = load_or_exit(&options.data_path, options);
+    apply_filters(&mut a, options);
+    let mut b = load_or_exit(other_path, options);
+    apply_filters(&mut b, options);
 
-        println!();
+    let comparison = Comparison::new(&a, &b, options.top);
+    match options.format {
+        Format::Console => comparison.print(&options.data_path, other_path),
+        format => print_structured(&comparison, format)?,
     }
 
     Ok(())
 }
 
-fn load_models(data_path_str: &str) -> Result<Models> {
+fn load_models(data_path_str: &str, parse_options: &ParseOptions) -> Result<Models> {
     if !USE_CACHE {
-        println!(
+        eprintln!(
             "{}",
             "Not using cache because constant USE_CACHE is false".yellow()
         );
-        return parse(data_path_str);
+        return parse(data_path_str, parse_options);
     }
 
-    let data_path = Path::new(data_path_str);
-    let data_filename = data_path.file_name().unwrap().to_str().unwrap();
-    let cache_path = Path::new(data_path_str)
-        .parent()
-        .unwrap()
-        .join(data_filename.to_owned() + ".cache.json");
+    if parse_options.compact {
+        // Compact models are missing titles and URLs, so they mustn't be
+        // cached or loaded from a full cache
+        return parse(data_path_str, parse_options);
+    }
+
+    if parse_options.include_ads {
+        // The cache is written without ads
+        return parse(data_path_str, parse_options);
+    }
+
+    if parse_options.channel_conflicts != ChannelConflictPolicy::default() {
+        // The cache is written with the default policy, and conflicts are
+        // resolved before it's written
+        return parse(data_path_str, parse_options);
+    }
+
+    if parse_options.skip_errors {
+        // A cache written without the bad rows would hide them from later
+        // runs that don't skip errors
+        return parse(data_path_str, parse_options);
+    }
+
+    let cache_path = cache_path(data_path_str, "");
 
     // Try loading cache
-    return load_cache(&cache_path).or_else(|e| {
+    load_cache(&cache_path).or_else(|e| {
         // Fallback to parsing data from source file
-        println!(
+        eprintln!(
             "{} {}",
             "Couldn't use cache data:".dimmed(),
             e.to_string().dimmed()
         );
 
-        let models = parse(data_path_str)?;
+        let models = parse(data_path_str, parse_options)?;
+
+        // An empty history is more likely a misread export than a real one,
+        // so it isn't cached and the next run parses again
+        if !models.is_empty() && write_cache(&cache_path, &models)? {
+            eprintln!(
+                "{} {}",
+                "Wrote cache to".dimmed(),
+                cache_path.to_str().unwrap().white()
+            );
+        }
+
+        Ok(models)
+    })
+}
+
+/// The cache file next to the data file, `<file><suffix>.cache.json`.
+fn cache_path(data_path_str: &str, suffix: &str) -> PathBuf {
+    let data_path = Path::new(data_path_str);
+    let data_filename = data_path.file_name().unwrap().to_str().unwrap();
+
+    data_path
+        .parent()
+        .unwrap()
+        .join(format!("{}{}.cache.json", data_filename, suffix))
+}
+
+/// The cache for the `--since`/`--until` window,
+/// `<file>.<since>_<until>.cache.json`.
+fn frozen_range_cache_path(data_path_str: &str, options: &Options) -> PathBuf {
+    let label = |date: Option<chrono::NaiveDate>, open: &str| {
+        date.map_or(open.to_string(), |date| date.to_string())
+    };
+    let suffix = format!(
+        ".{}_{}",
+        label(options.since, "start"),
+        label(options.until, "end")
+    );
+
+    cache_path(data_path_str, &suffix)
+}
+
+/// The cache `load_or_exit` reads and writes with `options`, if any.
+fn active_cache_path(options: &Options) -> Option<PathBuf> {
+    if !USE_CACHE
+        || options.counts_only_memory
+        || options.include_ads
+        || options.channel_conflicts != ChannelConflictPolicy::default()
+        || options.skip_errors
+    {
+        None
+    } else if options.freeze_range {
+        Some(frozen_range_cache_path(&options.data_path, options))
+    } else {
+        Some(cache_path(&options.data_path, ""))
+    }
+}
+
+/// Prints where the cache for `options` is and what's known about it without
+/// reading it, for `--cache-info`.
+fn print_cache_info(options: &Options) {
+    let Some(cache_path) = active_cache_path(options) else {
+        println!("{}", "No cache is used with these options".dimmed());
+        return;
+    };
+    println!("{} {}", "Cache:".dimmed(), cache_path.display());
+
+    let metadata = match std::fs::metadata(&cache_path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            println!("{}", "Doesn't exist yet, the next run writes it".dimmed());
+            return;
+        }
+        Err(error) => {
+            println!("{} {}", "Not readable:".dimmed(), error);
+            return;
+        }
+    };
+    println!("{} {} bytes", "Size:".dimmed(), metadata.len());
+
+    let Ok(modified) = metadata.modified() else {
+        return;
+    };
+    println!(
+        "{} {}",
+        "Modified:".dimmed(),
+        chrono::DateTime::<chrono::Local>::from(modified).format("%Y-%m-%d %H:%M:%S")
+    );
 
-        let mut file = File::create(&cache_path)?;
-        write!(file, "{}", models.to_string())?;
+    // The cache is used no matter how old it is, so a newer history means the
+    // numbers are from the old one
+    let data_modified = std::fs::metadata(&options.data_path).and_then(|m| m.modified());
+    if data_modified.is_ok_and(|data_modified| data_modified > modified) {
         println!(
+            "{}",
+            "The history file is newer than the cache, delete the cache to use it".yellow()
+        );
+    }
+}
+
+/// Loads a cache holding only the watches within `--since`/`--until`,
+/// building it from the full history on the first run. The frozen cache is
+/// derived from the full one, delete it to pick up a new export.
+fn load_frozen_range(data_path_str: &str, options: &Options) -> Result<Models> {
+    if active_cache_path(options).is_none() {
+        // Options that keep the full cache from being used, like
+        // --include-ads, keep the window from being frozen too
+        let mut models = load_models(data_path_str, &options.parse_options())?;
+        models.remove_watches_outside(options.since, options.until);
+        return Ok(models);
+    }
+    let cache_path = frozen_range_cache_path(data_path_str, options);
+
+    load_cache(&cache_path).or_else(|e| {
+        eprintln!(
             "{} {}",
-            "Wrote cache to".dimmed(),
-            cache_path.to_str().unwrap().white()
+            "Couldn't use frozen range cache:".dimmed(),
+            e.to_string().dimmed()
         );
 
+        let mut models = load_models(data_path_str, &options.parse_options())?;
+        models.remove_watches_outside(options.since, options.until);
+
+        // Like the full cache, an empty window isn't frozen
+        if !models.is_empty() && write_cache(&cache_path, &models)? {
+            eprintln!(
+                "{} {}",
+                "Wrote frozen range cache to".dimmed(),
+                cache_path.to_str().unwrap().white()
+            );
+        }
+
         Ok(models)
-    });
+    })
+}
+
+/// Writes the cache to a temporary file and renames it into place, so an
+/// interrupted write never leaves a truncated cache behind. Returns false
+/// without writing if another run holds the cache's lock file, so two runs
+/// never write the same temporary file.
+fn write_cache(cache_path: &Path, models: &Models) -> Result<bool> {
+    let lock_path = cache_path.with_extension("json.lock");
+    let _lock = match CacheLock::acquire(&lock_path) {
+        Ok(lock) => lock,
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+            eprintln!(
+                "{} {}",
+                "Another run is writing the cache, not caching. If none is, delete".yellow(),
+                lock_path.to_str().unwrap().white()
+            );
+            return Ok(false);
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let temp_path = cache_path.with_extension("json.tmp");
+
+    let mut file = BufWriter::new(File::create(&temp_path)?);
+    models.to_writer(&mut file)?;
+    file.into_inner()?.sync_all()?;
+    std::fs::rename(&temp_path, cache_path)?;
+
+    Ok(true)
+}
+
+/// A lock file held while writing a cache, removed when dropped, even if the
+/// write failed.
+struct CacheLock {
+    path: PathBuf,
+}
+
+impl CacheLock {
+    /// Creates the lock file, failing with `AlreadyExists` if it's held.
+    fn acquire(path: &Path) -> std::io::Result<CacheLock> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+
+        Ok(CacheLock {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
 }
 
 fn print_usage() {
-    println!("Usage: {} [file]", COMMAND_NAME);
+    println!("Usage: {} [options] [file]", COMMAND_NAME);
+    println!("       {} compare [options] file file", COMMAND_NAME);
+    println!();
+    println!("Options:");
+    println!("  --collapse-window MINUTES  Count repeat watches of a video within MINUTES once");
+    println!("  --since DATE               Only include watches on or after DATE (YYYY-MM-DD)");
+    println!("  --until DATE               Only include watches on or before DATE");
+    println!("  --freeze-range             Cache the --since/--until window separately for faster reruns");
+    println!("  --cache-info               Print where the cache is, its size and age, without loading it");
+    println!(
+        "  --prune                    Drop videos and channels without watches from the cache"
+    );
+    println!("  --sample N                 Only use a random N watches, for a quick look at a large history");
+    println!("  --seed SEED                Pick the same --sample each run");
+    println!("  --group-by PERIOD          Count watches per day, week, month, quarter or year");
+    println!("  --hour-profile             Show when in the day you watch, for each weekday");
+    println!("  --watches-per-channel-histogram  Show how many channels you watched once, 2-5 times, and so on");
+    println!("  --bins LIST                Comma separated upper ends of the histogram's bins (default 1,5,10,50)");
+    println!("  --outlier-k K              List days over K standard deviations above the daily mean (default 3)");
+    println!("  --min-year-watches N       Leave channels watched fewer than N times in a year out of that year");
+    println!("  --weekend DAYS             Days counted as the weekend, default Sat,Sun");
+    println!("  --utc                      Use UTC for dates, hours and weekdays instead of each watch's local time");
+    println!("  --upload-dates FILE        Show how old videos were when watched, from a JSON file of upload dates");
+    println!("  --enrich                   Look up upload dates and durations with the YouTube Data API, for video age and watch time (needs the youtube-api feature)");
+    println!("  --api-key KEY              YouTube Data API key for --enrich, instead of $YT_HISTORY_API_KEY");
+    println!("  --top N                    Number of videos and channels to list (default 50)");
+    println!("  --videos-per-channel M     List the top M videos of each top channel");
+    println!(
+        "  --page N                   Only list page N of all videos and channels, by watches"
+    );
+    println!("  --period NAME=START:END    Report the top lists for a named date range instead (repeatable)");
+    println!("  --page-size M              Entries per page with --page (default: --top)");
+    println!(
+        "  --format FORMAT            Print the report as console (default), json, toml or yaml"
+    );
+    println!(
+        "  --channel-conflicts POLICY Keep the first (default) or last channel seen for a video (last isn't cached)"
+    );
+    println!("  --locale LANG              Language of an HTML export: en, de, es or fr (detected by default)");
+    println!("  --encoding ENCODING        utf-8 (default), or windows-1252 to decode invalid UTF-8 in old HTML exports");
+    println!("  --lossy                    Replace invalid UTF-8 in an HTML export with U+FFFD instead of failing");
+    println!(
+        "  --include-ads              Keep watches marked as ads in a JSON export (not cached)"
+    );
+    println!(
+        "  --exclude-channel NAME     Leave out a channel by name, ignoring case (repeatable)"
+    );
+    println!("  --exclude-shorts           Leave YouTube Shorts out of the report");
+    println!("  --only-shorts              Only report on YouTube Shorts");
+    println!("  --profile                  Print how long reading, parsing and reporting took");
+    println!("  --validate                 Print data quality checks");
+    println!("  --drop-future              Remove watches dated in the future");
+    println!("  --sort ORDER               Order exported watches chronologically (default) or as in the file");
+    println!(
+        "  --group-channels-by-handle  Merge /channel, /@, /user and /c URLs of the same channel"
+    );
+    println!("  --counts-only-memory       Keep only IDs and counts to save memory, titles show as IDs (no cache or exports)");
+    println!("  --count-only               Only print the number of videos and watches");
+    println!("  --summary-only             Only print the summary at the top of the report, without the lists");
+    println!("  --watch-count-of URL       Only print how many times, and when, URL was watched");
+    println!(
+        "  --on MM-DD                 Only print the videos watched on this day, in every year"
+    );
+    println!("  --on-this-day              Like --on with today's date");
+    println!("  --find-dupes               Only print videos with the same title at different URLs, like re-uploads");
+    println!("  --list-channels            Only print every channel as count, name and URL, separated by tabs");
+    println!("  --list-videos              Only print every video as count, title, channel and URL. With --csv, writes them there instead");
+    println!("  --first-watch-of CHANNEL   Only print the first watch from CHANNEL (URL, ID, @handle or name)");
+    println!("  --last-watch-of CHANNEL    Only print the last watch from CHANNEL");
+    println!("  --columns LIST             Comma separated CSV columns, see the README");
+    println!("  --round-time UNIT          Round exported timestamps to the nearest hour or day");
+    println!("  --csv FILE                 Export watches to a CSV file");
+    println!(
+        "  --channel-timeline FILE    Export the date of each channel's first watch to a CSV file"
+    );
+    println!("  --timeline-csv FILE        Export the number of watches on each day to a CSV file");
+    println!("  --ids-only                 Use video and channel IDs instead of URLs in CSV and JSON output");
+    println!("  --parquet FILE             Export watches to a Parquet file (requires the `parquet` feature)");
 }
 
 fn load_cache(cache_path: &PathBuf) -> Result<Models> {
     let start = Instant::now();
-    let mut file = File::open(cache_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    let models = Models::from_str(contents)?;
-    println!(
+    let file = BufReader::new(File::open(cache_path)?);
+    let models = Models::from_reader(file)?;
+    eprintln!(
         "{} {:.2?}",
         "Loaded cache data in".dimmed(),
         start.elapsed()
@@ -184,26 +1045,112 @@ fn load_cache(cache_path: &PathBuf) -> Result<Models> {
     Ok(models)
 }
 
-fn parse(file_path: &str) -> Result<Models> {
-    println!("{} {}", "Reading file".dimmed(), file_path.bold());
+/// Prints how much of the input was read, so a truncated download stands
+/// out.
+fn print_parse_summary(stats: &ParseStats) {
+    let mut parts = Vec::new();
+    if let Some(bytes) = stats.bytes {
+        parts.push(format!("{} bytes", bytes));
+    }
+    if let Some(chars) = stats.chars {
+        parts.push(format!("{} characters", chars));
+    }
+    parts.push(format!("{} rows", stats.rows));
+    if stats.ads > 0 {
+        parts.push(format!("{} ads", stats.ads));
+    }
+    parts.push(format!("{} skipped", stats.skipped.len()));
+
+    eprintln!("{} {}", "Read".dimmed(), parts.join(", ").dimmed());
+}
+
+fn parse(file_path: &str, parse_options: &ParseOptions) -> Result<Models> {
+    eprintln!("{} {}", "Reading file".dimmed(), file_path.bold());
 
     let file_type = if file_path.ends_with(".json") {
         parser::ParserType::Json
+    } else if file_path.ends_with(".zip") {
+        parser::ParserType::Zip
     } else {
         parser::ParserType::Html
     };
 
     let start = Instant::now();
 
-    let result = parser::parse_file(file_path, file_type);
+    // On a terminal, keep redrawing one line with the count. Otherwise, like
+    // when logging to a file, print a line now and then.
+    let live = std::io::stderr().is_terminal();
+    let mut redrawn = false;
+    let progress = |rows: usize| {
+        if live && rows.is_multiple_of(LIVE_PROGRESS_EVERY) {
+            eprint!("\r{} {} {}", "Parsed".dimmed(), rows, "rows".dimmed());
+            redrawn = true;
+        } else if !live && rows.is_multiple_of(PROGRESS_EVERY) {
+            eprintln!("{} {} {}", "Parsed".dimmed(), rows, "rows".dimmed());
+        }
+    };
+    let result = parser::parse_file(file_path, file_type, parse_options, progress);
+    if redrawn {
+        // Clear the live count so later lines start clean
+        eprint!("\r\x1b[2K");
+    }
     match result {
-        Ok(models) => {
-            println!("{} {:.2?}", "Parsed data in".dimmed(), start.elapsed());
+        Ok((models, stats)) => {
+            if let Some(entry) = &stats.zip_entry {
+                eprintln!("{} {}", "Using archive entry".dimmed(), entry.bold());
+            }
+            eprintln!("{} {:.2?}", "Parsed data in".dimmed(), start.elapsed());
+            print_parse_summary(&stats);
+
+            if parse_options.profile {
+                print_profile("read", stats.read);
+                print_profile("decode + tokenize", stats.tokenize);
+                print_profile("model build", stats.build);
+            }
+
+            if !stats.skipped.is_empty() {
+                for error in stats.skipped.iter().take(SKIPPED_SHOWN) {
+                    eprintln!("  {}", error.dimmed());
+                }
+                eprintln!(
+                    "{} {} {}",
+                    "Skipped".yellow(),
+                    stats.skipped.len(),
+                    "rows that couldn't be read".yellow()
+                );
+            }
+
+            if models.channel_conflicts() > 0 {
+                eprintln!(
+                    "{} {} {}",
+                    "Found".dimmed(),
+                    models.channel_conflicts(),
+                    "videos listed under more than one channel".dimmed()
+                );
+            }
+
+            if models.empty_titles() > 0 {
+                eprintln!(
+                    "{} {} {}",
+                    "Found".dimmed(),
+                    models.empty_titles(),
+                    "rows with an empty title".dimmed()
+                );
+            }
+
+            // Only HTML counts bytes. Rows are found by a link in the
+            // export's language, so a wrong --locale finds none.
+            if stats.rows == 0 && stats.bytes.is_some_and(|bytes| bytes > 0) {
+                eprintln!(
+                    "{}",
+                    "No watch row markers were recognised in the HTML, check --locale".yellow()
+                );
+            }
 
             Ok(models)
         }
         Err(e) => {
-            println!("{} {:.2?}", "Errored in".dimmed(), start.elapsed());
+            eprintln!("{} {:.2?}", "Errored in".dimmed(), start.elapsed());
 
             if let Some(e) = e.downcast_ref::<ParseError>() {
                 println!("{} {}", "Error parsing file".red(), file_path.bold());
@@ -216,6 +1163,69 @@ fn parse(file_path: &str) -> Result<Models> {
     }
 }
 
+/// Prints the input around where `error` happened, with a caret under the
+/// character the parser stopped at.
+fn explain_parse_error(data_path: &str, error: &ParseError) {
+    let Some(location) = error.location() else {
+        println!("{}", "No position to show for this error".dimmed());
+        return;
+    };
+    if data_path.ends_with(".zip") {
+        println!(
+            "{}",
+            "--explain needs the extracted file, not the .zip".dimmed()
+        );
+        return;
+    }
+
+    let bytes = match std::fs::read(data_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("{} {}", "Couldn't read the file to explain:".dimmed(), e);
+            return;
+        }
+    };
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(&text);
+
+    // `chars` counts characters read, which is the index of the next one
+    let at = location.chars;
+    let start = at.saturating_sub(EXPLAIN_CONTEXT);
+    // Newlines would break up the caret line, show them as a symbol instead
+    let visible = |c: char| match c {
+        '\n' => '↵',
+        c if c.is_whitespace() => ' ',
+        c => c,
+    };
+    let before = text
+        .chars()
+        .skip(start)
+        .take(at - start)
+        .map(visible)
+        .collect::<String>();
+    let after = text
+        .chars()
+        .skip(at)
+        .take(EXPLAIN_CONTEXT + 1)
+        .map(visible)
+        .collect::<String>();
+
+    println!();
+    println!(
+        "{} {} {} {}",
+        "Input around line".dimmed(),
+        location.lines,
+        "column".dimmed(),
+        location.columns
+    );
+    println!("{}{}", before, after);
+    println!("{}{}", " ".repeat(before.chars().count()), "^".red().bold());
+}
+
+fn print_profile(step: &str, duration: Duration) {
+    eprintln!("{} {:.2?}", format!("  {:<18}", step).dimmed(), duration);
+}
+
 fn print_parse_error(error: &ParseError) {
     match error {
         ParseError::UnterminatedInput { expected, closest } => {
@@ -231,9 +1241,7 @@ fn print_parse_error(error: &ParseError) {
             if let Some((closest, location)) = closest {
                 println!(
                     "Closest: {} at line {} column {}",
-                    closest,
-                    location.lines + 1,
-                    location.columns + 1
+                    closest, location.lines, location.columns
                 );
             }
         }
@@ -244,8 +1252,11 @@ fn print_parse_error(error: &ParseError) {
             );
             println!("Bytes: {:?}", bytes);
         }
-        ParseError::IoError { error, .. } => {
-            println!("IO error: {}", error);
+        ParseError::IoError { location, error } => {
+            println!(
+                "IO error at line {} column {}: {}",
+                location.lines, location.columns, error
+            );
         }
         ParseError::DateParseError {
             location,
@@ -282,8 +1293,8 @@ fn print_parse_error(error: &ParseError) {
 
             println!("{}", error);
         }
-        ParseError::NoRows => {
-            println!("No rows found");
+        ParseError::MissingDate { .. } => {
+            println!("{}", error);
         }
     }
 }
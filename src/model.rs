@@ -1,6 +1,6 @@
-use chrono::{Datelike, FixedOffset, Utc};
+use chrono::{Datelike, FixedOffset, NaiveDate, Timelike, Utc, Weekday};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
 use std::rc::Rc;
 
@@ -10,7 +10,9 @@ trait Model {
     fn id(&self) -> &Self::Id;
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Channels are equal, and hash, by URL alone, so a channel from another
+/// history or with a changed name is still the same channel.
+#[derive(Debug, Clone)]
 pub struct Channel {
     pub url: String,
     pub name: String,
@@ -24,13 +26,58 @@ impl Model for Channel {
     }
 }
 
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Channel {}
+
+impl Hash for Channel {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl Channel {
+    /// The `UC...` channel ID, if the URL is in the `/channel/<id>` form.
+    pub fn channel_id(&self) -> Option<&str> {
+        channel_id_from_url(&self.url)
+    }
+
+    /// The channel ID, or the URL if it doesn't contain one.
+    pub fn id_or_url(&self) -> &str {
+        self.channel_id().unwrap_or(&self.url)
+    }
+
+    /// The handle or legacy username from a `/@<handle>`, `/user/<name>` or
+    /// `/c/<name>` URL.
+    pub fn handle(&self) -> Option<&str> {
+        let (_, rest) = self
+            .url
+            .split_once("/@")
+            .or_else(|| self.url.split_once("/user/"))
+            .or_else(|| self.url.split_once("/c/"))?;
+        let handle = rest.split(['/', '?', '#']).next()?;
+
+        if handle.is_empty() {
+            None
+        } else {
+            Some(handle)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ScalarChannel {
     url: String,
     name: String,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Like `Channel`, videos are equal, and hash, by URL alone. Compare `title`
+/// and `channel` separately when they matter.
+#[derive(Debug, Clone)]
 pub struct Video {
     pub url: String,
     pub title: String,
@@ -45,6 +92,71 @@ impl Model for Video {
     }
 }
 
+impl PartialEq for Video {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Video {}
+
+impl Hash for Video {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl Video {
+    /// Shorts are watched through `/shorts/<id>` URLs.
+    pub fn is_short(&self) -> bool {
+        self.url.contains("/shorts/")
+    }
+
+    /// YouTube Music plays link to `music.youtube.com`, in both export
+    /// formats.
+    pub fn is_music(&self) -> bool {
+        self.url.contains("://music.youtube.com/")
+    }
+
+    /// The 11 character video ID from a `watch?v=`, `/shorts/` or `youtu.be`
+    /// URL.
+    pub fn video_id(&self) -> Option<&str> {
+        video_id_from_url(&self.url)
+    }
+
+    /// The video ID, or the URL if it doesn't contain one.
+    pub fn id_or_url(&self) -> &str {
+        self.video_id().unwrap_or(&self.url)
+    }
+}
+
+/// The video ID from a `watch?v=`, `/shorts/` or `youtu.be` URL.
+pub fn video_id_from_url(url: &str) -> Option<&str> {
+    let (_, rest) = url
+        .split_once("watch?v=")
+        .or_else(|| url.split_once("/shorts/"))
+        .or_else(|| url.split_once("youtu.be/"))?;
+    let id = rest.split(['&', '#', '/', '?']).next()?;
+
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+/// The `UC...` channel ID from a `/channel/<id>` URL.
+pub fn channel_id_from_url(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("/channel/")?;
+    let id = rest.split(['/', '?', '#']).next()?;
+
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ScalarVideo {
     url: String,
@@ -56,23 +168,107 @@ struct ScalarVideo {
 pub struct Watched {
     pub video: Rc<Video>,
     pub when: chrono::DateTime<FixedOffset>,
+    /// The source didn't identify a single offset for `when`, for example the
+    /// "CST" abbreviation is used by several timezones.
+    pub offset_ambiguous: bool,
+}
+
+/// The days `Watched::is_weekend` counts unless told otherwise.
+pub const DEFAULT_WEEKEND: [Weekday; 2] = [Weekday::Sat, Weekday::Sun];
+
+impl Watched {
+    /// The calendar date of the watch in its stored offset.
+    pub fn local_date(&self) -> NaiveDate {
+        self.when.date_naive()
+    }
+
+    /// The hour of the watch (0 to 23) in its stored offset.
+    pub fn local_hour(&self) -> u32 {
+        self.when.hour()
+    }
+
+    /// Whether the watch's local date falls on one of the `weekend` days.
+    pub fn is_weekend(&self, weekend: &[Weekday]) -> bool {
+        weekend.contains(&self.local_date().weekday())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ScalarWatched {
     video: <Video as Model>::Id,
+    #[serde(with = "rfc3339")]
     when: chrono::DateTime<FixedOffset>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    offset_ambiguous: bool,
 }
 
-#[derive(Debug)]
+/// Reads and writes cache timestamps as RFC 3339 with the original offset,
+/// rather than relying on chrono's default serde format.
+mod rfc3339 {
+    use chrono::{DateTime, FixedOffset, SecondsFormat};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        when: &DateTime<FixedOffset>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&when.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<FixedOffset>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&s).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Models {
     watches: Vec<Watched>,
     channels: HashMap<<Channel as Model>::Id, Rc<Channel>>,
     videos: HashMap<<Video as Model>::Id, Rc<Video>>,
+    channel_conflict_policy: ChannelConflictPolicy,
+    /// URLs of videos inserted with more than one channel.
+    channel_conflicts: HashSet<String>,
+    /// URLs of channels that lost a video to `ChannelConflictPolicy::Last`,
+    /// until `resolve_channel_conflicts` drops the ones left without videos.
+    replaced_channels: HashSet<String>,
+    empty_titles: usize,
+    compact: bool,
+}
+
+/// Which channel to keep when the same video URL is inserted with different
+/// channels, for example after a re-upload or a channel URL change. First and
+/// last refer to insertion order.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ChannelConflictPolicy {
+    #[default]
+    First,
+    Last,
+}
+
+impl std::str::FromStr for ChannelConflictPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(ChannelConflictPolicy::First),
+            "last" => Ok(ChannelConflictPolicy::Last),
+            _ => Err(()),
+        }
+    }
 }
 
+/// Version of the cache JSON format, bumped whenever its shape changes. See
+/// "Cache format" in the README.
+pub const CACHE_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ScalarModels {
+    /// Missing in caches written before the format was versioned.
+    #[serde(default)]
+    version: u32,
     watches: Vec<ScalarWatched>,
     channels: Vec<ScalarChannel>,
     videos: Vec<ScalarVideo>,
@@ -84,47 +280,215 @@ impl Models {
             watches: Vec::new(),
             channels: HashMap::new(),
             videos: HashMap::new(),
+            channel_conflict_policy: ChannelConflictPolicy::default(),
+            channel_conflicts: HashSet::new(),
+            replaced_channels: HashSet::new(),
+            empty_titles: 0,
+            compact: false,
         }
     }
 
+    pub fn set_channel_conflict_policy(&mut self, policy: ChannelConflictPolicy) {
+        self.channel_conflict_policy = policy;
+    }
+
+    /// When set, `find_or_create_channel` and `find_or_create_video` store IDs
+    /// in place of URLs, titles and names to save memory. Counts are
+    /// unaffected, but the original text is lost.
+    pub fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    /// The number of videos that were inserted with a different channel than
+    /// the one they already had, however many rows disagreed. Not stored in
+    /// the cache.
+    pub fn channel_conflicts(&self) -> usize {
+        self.channel_conflicts.len()
+    }
+
+    /// The number of rows inserted with an empty title, which were given a
+    /// placeholder. Not stored in the cache.
+    pub fn empty_titles(&self) -> usize {
+        self.empty_titles
+    }
+
+    /// All watches, in the order they were inserted.
+    pub fn watches(&self) -> &[Watched] {
+        &self.watches
+    }
+
+    /// All watches, oldest first. Watches at the same instant keep their
+    /// insertion order.
+    pub fn watches_sorted(&self) -> Vec<&Watched> {
+        let mut watches = self.watches.iter().collect::<Vec<_>>();
+        watches.sort_by_key(|watched| watched.when);
+
+        watches
+    }
+
+    /// All channels, sorted by name and then URL.
+    #[cfg(test)]
+    pub fn all_channels_sorted(&self) -> Vec<Rc<Channel>> {
+        let mut channels = self.channels.values().cloned().collect::<Vec<_>>();
+        channels.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.url.cmp(&b.url)));
+
+        channels
+    }
+
+    /// All videos, sorted by title and then URL.
+    pub fn all_videos_sorted(&self) -> Vec<Rc<Video>> {
+        let mut videos = self.videos.values().cloned().collect::<Vec<_>>();
+        videos.sort_by(|a, b| a.title.cmp(&b.title).then_with(|| a.url.cmp(&b.url)));
+
+        videos
+    }
+
+    /// Counts watches whose timestamp goes against the overall direction of
+    /// the input (newest first for Takeout files). A high count usually means
+    /// offsets were parsed incorrectly, for example around DST changes.
+    pub fn count_out_of_order_watches(&self) -> usize {
+        let pairs = self.watches.windows(2);
+        let descending = pairs.clone().filter(|w| w[0].when > w[1].when).count();
+        let ascending = pairs.filter(|w| w[0].when < w[1].when).count();
+
+        descending.min(ascending)
+    }
+
+    /// Counts watches dated after `after`. Only a timezone bug or bad data can
+    /// put a watch in the future, and it would skew anything about recent
+    /// watches.
+    pub fn count_future_watches(&self, after: chrono::DateTime<Utc>) -> usize {
+        self.watches_sorted()
+            .iter()
+            .rev()
+            .take_while(|watched| watched.when > after)
+            .count()
+    }
+
     pub fn count_videos(&self, where_video: WhereVideo) -> u64 {
         match where_video {
-            WhereVideo::Structure(matcher) => {
-                return self
-                    .videos
-                    .iter()
-                    .filter(|(_, video)| matcher.eq(*video))
-                    .count() as u64;
-            }
+            WhereVideo::Structure(matcher) => self
+                .videos
+                .iter()
+                .filter(|(_, video)| matcher.eq(*video))
+                .count() as u64,
             WhereVideo::Reference(video) => {
                 if self.videos.contains_key(video.id()) {
-                    return 1;
+                    1
                 } else {
-                    return 0;
+                    0
                 }
             }
-            WhereVideo::Any => {
-                return self.videos.len() as u64;
-            }
+            WhereVideo::Any => self.videos.len() as u64,
         }
     }
 
+    /// Returns how many distinct videos belong to channels matching
+    /// `where_channel`, without building a `VideoMatcher` for it.
+    #[cfg(test)]
+    pub fn count_videos_for_channel(&self, where_channel: WhereChannel) -> u64 {
+        self.videos
+            .values()
+            .filter(|video| where_channel.matches(video.channel.clone()))
+            .count() as u64
+    }
+
     pub fn count_watches(&self, where_watch: WhereWatched) -> u64 {
         match where_watch {
-            WhereWatched::Structure(matcher) => {
-                return self
-                    .watches
-                    .iter()
-                    .filter(|watched| matcher.eq(watched))
-                    .count() as u64;
-            }
+            WhereWatched::Structure(matcher) => self
+                .watches
+                .iter()
+                .filter(|watched| matcher.eq(watched))
+                .count() as u64,
             WhereWatched::Reference(_) => {
                 todo!();
             }
-            WhereWatched::Any => {
-                return self.watches.len() as u64;
+            WhereWatched::Any => self.watches.len() as u64,
+        }
+    }
+
+    /// Videos in both `self` and `other`, by URL, sorted by URL. The returned
+    /// videos are `self`'s.
+    pub fn common_videos(&self, other: &Models) -> Vec<Rc<Video>> {
+        intersect(&self.videos, &other.videos, true)
+    }
+
+    /// Channels in both `self` and `other`, by URL, sorted by URL. The returned
+    /// channels are `self`'s.
+    pub fn common_channels(&self, other: &Models) -> Vec<Rc<Channel>> {
+        intersect(&self.channels, &other.channels, true)
+    }
+
+    /// Videos and channels in `self` but not in `other`, by URL, sorted by URL.
+    pub fn unique_to(&self, other: &Models) -> (Vec<Rc<Video>>, Vec<Rc<Channel>>) {
+        (
+            intersect(&self.videos, &other.videos, false),
+            intersect(&self.channels, &other.channels, false),
+        )
+    }
+
+    /// Returns the number of watches of videos matching `where_video`, without
+    /// building the per-video map `count_watched_by_video` does.
+    #[cfg(test)]
+    pub fn watch_count(&self, where_video: WhereVideo) -> usize {
+        self.watches
+            .iter()
+            .filter(|watched| where_video.matches(watched.video.clone()))
+            .count()
+    }
+
+    /// Watches of the video at `url`, oldest first. URLs are compared by video
+    /// ID, so `youtu.be/<id>` finds watches of `watch?v=<id>`.
+    pub fn watches_of(&self, url: &str) -> Vec<&Watched> {
+        let id = video_id_from_url(url).unwrap_or(url);
+
+        let mut watches = self
+            .watches
+            .iter()
+            .filter(|watched| watched.video.id_or_url() == id)
+            .collect::<Vec<_>>();
+        watches.sort_by_key(|watched| watched.when);
+
+        watches
+    }
+
+    /// Watches of videos from `channel`, oldest first. `channel` can be the
+    /// channel's URL, its `UC...` ID, its handle (with or without "@") or its
+    /// name.
+    pub fn watches_of_channel(&self, channel: &str) -> Vec<&Watched> {
+        let handle = channel.strip_prefix('@').unwrap_or(channel);
+        let matches = |c: &Channel| {
+            c.url == channel
+                || c.channel_id() == Some(channel)
+                || c.handle().is_some_and(|h| h.eq_ignore_ascii_case(handle))
+                || c.name == channel
+        };
+
+        let mut watches = self
+            .watches
+            .iter()
+            .filter(|watched| matches(&watched.video.channel))
+            .collect::<Vec<_>>();
+        watches.sort_by_key(|watched| watched.when);
+
+        watches
+    }
+
+    /// Watches on `day` in their local date, grouped by year, oldest first.
+    pub fn watches_on_day(&self, day: MonthDay) -> BTreeMap<i32, Vec<&Watched>> {
+        let mut years: BTreeMap<i32, Vec<&Watched>> = BTreeMap::new();
+
+        for watched in self.watches.iter() {
+            let date = watched.local_date();
+            if MonthDay::of(date) == day {
+                years.entry(date.year()).or_default().push(watched);
             }
         }
+        for watches in years.values_mut() {
+            watches.sort_by_key(|watched| watched.when);
+        }
+
+        years
     }
 
     pub fn count_watched_by_video(&self) -> HashMap<String, (usize, Rc<Video>)> {
@@ -135,7 +499,7 @@ impl Models {
                 .entry(watched.video.id().clone())
                 .or_insert((0, watched.video.clone()));
 
-            (*count).0 += 1;
+            count.0 += 1;
         }
 
         counts
@@ -149,12 +513,127 @@ impl Models {
                 .entry(watched.video.channel.id().clone())
                 .or_insert((0, watched.video.channel.clone()));
 
-            (*count).0 += 1;
+            count.0 += 1;
+        }
+
+        counts
+    }
+
+    /// Returns the `n` most watched videos, most watched first. Ties are sorted
+    /// by title, then URL.
+    pub fn top_videos(&self, n: usize) -> Vec<(usize, Rc<Video>)> {
+        let mut counts = self
+            .count_watched_by_video()
+            .into_values()
+            .collect::<Vec<_>>();
+        rank_videos(&mut counts);
+        counts.truncate(n);
+
+        counts
+    }
+
+    /// Returns the `n` most watched channels, most watched first. Ties are
+    /// sorted by name, then URL.
+    pub fn top_channels(&self, n: usize) -> Vec<(usize, Rc<Channel>)> {
+        let mut counts = self
+            .count_watched_by_channel()
+            .into_values()
+            .collect::<Vec<_>>();
+        rank_channels(&mut counts);
+        counts.truncate(n);
+
+        counts
+    }
+
+    /// Counts channels by their total watches, into one bin ending at each of
+    /// `bins` (inclusive) and a last one for everything above. With `bins` of
+    /// `[1, 5]` that's channels with 1, 2 to 5, and over 5 watches. `bins`
+    /// must be ascending.
+    pub fn count_channels_by_watches(&self, bins: &[usize]) -> Vec<usize> {
+        let mut counts = vec![0; bins.len() + 1];
+        for (count, _) in self.count_watched_by_channel().into_values() {
+            counts[bins.partition_point(|&max| max < count)] += 1;
         }
 
         counts
     }
 
+    /// Groups watches by channel. Channels are ranked like `top_channels`, and
+    /// each channel's watches are oldest first, so none are empty.
+    pub fn iter_by_channel(&self) -> impl Iterator<Item = (Rc<Channel>, Vec<&Watched>)> {
+        let mut by_channel: HashMap<&String, (Rc<Channel>, Vec<&Watched>)> = HashMap::new();
+        for watched in self.watches_sorted() {
+            let channel = &watched.video.channel;
+            by_channel
+                .entry(channel.id())
+                .or_insert_with(|| (channel.clone(), Vec::new()))
+                .1
+                .push(watched);
+        }
+
+        let mut groups = by_channel.into_values().collect::<Vec<_>>();
+        groups.sort_by(|(a, a_watches), (b, b_watches)| {
+            b_watches
+                .len()
+                .cmp(&a_watches.len())
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.url.cmp(&b.url))
+        });
+
+        groups.into_iter()
+    }
+
+    /// Groups videos that share a title but not a URL, like re-uploads.
+    /// Titles are compared ignoring case and repeated whitespace. Groups are
+    /// sorted by title, and the videos in each group by URL.
+    pub fn duplicate_titles(&self) -> Vec<Vec<Rc<Video>>> {
+        let mut by_title: BTreeMap<String, Vec<Rc<Video>>> = BTreeMap::new();
+        for video in self.videos.values() {
+            let title = video.title.split_whitespace().collect::<Vec<_>>();
+            by_title
+                .entry(title.join(" ").to_lowercase())
+                .or_default()
+                .push(video.clone());
+        }
+
+        by_title
+            .into_values()
+            .filter(|videos| videos.len() > 1)
+            .map(|mut videos| {
+                videos.sort_by(|a, b| a.url.cmp(&b.url));
+                videos
+            })
+            .collect()
+    }
+
+    /// Returns the `channels` most watched channels, each with its `videos`
+    /// most watched videos. Both levels are ranked like `top_channels` and
+    /// `top_videos`.
+    pub fn top_videos_per_channel(
+        &self,
+        channels: usize,
+        videos: usize,
+    ) -> Vec<ChannelVideoCounts> {
+        let mut by_channel: HashMap<String, Vec<(usize, Rc<Video>)>> = HashMap::new();
+        for (count, video) in self.count_watched_by_video().into_values() {
+            by_channel
+                .entry(video.channel.url.clone())
+                .or_default()
+                .push((count, video));
+        }
+
+        self.top_channels(channels)
+            .into_iter()
+            .map(|(count, channel)| {
+                let mut channel_videos = by_channel.remove(&channel.url).unwrap_or_default();
+                rank_videos(&mut channel_videos);
+                channel_videos.truncate(videos);
+
+                (count, channel, channel_videos)
+            })
+            .collect()
+    }
+
     /// Returns a count of watches, per channel per year.
     ///
     /// HashMap<
@@ -170,151 +649,788 @@ impl Models {
         let mut counts: HashMap<i32, HashMap<String, (usize, Rc<Channel>)>> = HashMap::new();
 
         for watched in self.watches.iter() {
-            let year = watched.when.year();
+            let year = watched.local_date().year();
 
-            let channel_by_year = counts.entry(year).or_insert(HashMap::new());
+            let channel_by_year = counts.entry(year).or_default();
             let count = channel_by_year
                 .entry(watched.video.channel.id().clone())
                 .or_insert((0, watched.video.channel.clone()));
 
-            (*count).0 += 1;
+            count.0 += 1;
         }
 
         counts
     }
 
-    pub fn insert_watched(
-        &mut self,
-        when: chrono::DateTime<FixedOffset>,
-        video: WhereVideo,
-    ) -> Watched {
-        let video = self.find_video(video).unwrap().clone();
-        let watched = Watched { video, when };
-        self.watches.push(watched.clone());
+    /// The local date of the first watch from each channel, earliest first.
+    /// Channels first watched on the same day are sorted by name, then URL.
+    pub fn channel_discovery_dates(&self) -> Vec<(NaiveDate, Rc<Channel>)> {
+        let mut dates = self
+            .iter_by_channel()
+            .map(|(channel, watches)| (watches[0].local_date(), channel))
+            .collect::<Vec<_>>();
+        dates.sort_by(|(a_date, a), (b_date, b)| {
+            a_date
+                .cmp(b_date)
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.url.cmp(&b.url))
+        });
 
-        return watched;
+        dates
     }
 
-    pub fn insert_channel(&mut self, url: String, name: String) -> Rc<Channel> {
-        let channel = Rc::new(Channel { url, name });
-        self.channels.insert(channel.id().clone(), channel.clone());
+    /// Returns the most watched video of each local year. Ties are broken like
+    /// `top_videos`.
+    pub fn top_video_by_year(&self) -> BTreeMap<i32, (usize, Rc<Video>)> {
+        let mut counts: HashMap<i32, HashMap<String, (usize, Rc<Video>)>> = HashMap::new();
 
-        channel
+        for watched in self.watches.iter() {
+            let year = watched.local_date().year();
+
+            let count = counts
+                .entry(year)
+                .or_default()
+                .entry(watched.video.id().clone())
+                .or_insert((0, watched.video.clone()));
+
+            count.0 += 1;
+        }
+
+        counts
+            .into_iter()
+            .filter_map(|(year, videos)| {
+                let mut videos = videos.into_values().collect::<Vec<_>>();
+                rank_videos(&mut videos);
+                Some((year, videos.into_iter().next()?))
+            })
+            .collect()
     }
 
-    pub fn insert_video(&mut self, url: String, title: String, channel: WhereChannel) -> Rc<Video> {
-        let video = Rc::new(Video {
-            url,
-            title,
-            channel: self.find_channel(channel).unwrap().clone(),
-        });
+    /// Counts the different videos watched in each local year.
+    pub fn unique_videos_by_year(&self) -> BTreeMap<i32, usize> {
+        self.count_unique_by_year(|watched| watched.video.id())
+    }
 
-        self.videos.insert(video.id().clone(), video.clone());
+    /// Counts the different channels watched in each local year.
+    pub fn unique_channels_by_year(&self) -> BTreeMap<i32, usize> {
+        self.count_unique_by_year(|watched| watched.video.channel.id())
+    }
 
-        video
+    fn count_unique_by_year<'a>(
+        &'a self,
+        key: impl Fn(&'a Watched) -> &'a String,
+    ) -> BTreeMap<i32, usize> {
+        let mut seen: BTreeMap<i32, HashSet<&String>> = BTreeMap::new();
+        for watched in self.watches.iter() {
+            seen.entry(watched.local_date().year())
+                .or_default()
+                .insert(key(watched));
+        }
+
+        seen.into_iter()
+            .map(|(year, keys)| (year, keys.len()))
+            .collect()
     }
 
-    pub fn find_channel(&self, where_channel: WhereChannel) -> Option<Rc<Channel>> {
-        match where_channel {
-            WhereChannel::Structure(matcher) => {
-                if let Some(url) = matcher.url {
-                    return self.channels.get(url).map(|channel| channel.clone());
-                }
+    /// Counts watches by local weekday (Monday first) and hour.
+    pub fn count_watches_by_weekday_and_hour(&self) -> [[usize; 24]; 7] {
+        let mut counts = [[0; 24]; 7];
 
-                return self
-                    .channels
-                    .iter()
-                    .find(|(_, channel)| matcher.matches(*channel))
-                    .map(|(_, channel)| channel.clone());
-            }
-            WhereChannel::Reference(channel) => Some(channel),
-            WhereChannel::Any => self.channels.values().next().map(|channel| channel.clone()),
+        for watched in self.watches.iter() {
+            let weekday = watched.when.weekday().num_days_from_monday() as usize;
+            counts[weekday][watched.local_hour() as usize] += 1;
         }
+
+        counts
     }
 
-    pub fn find_video(&self, where_video: WhereVideo<'_>) -> Option<Rc<Video>> {
-        match where_video {
-            WhereVideo::Structure(matcher) => {
-                if let Some(url) = matcher.url {
-                    return self.videos.get(url).map(|video| video.clone());
+    /// Like `count_watches_by_weekday_and_hour`, but each weekday's hours are
+    /// fractions of that weekday's watches, so weekdays can be compared by
+    /// shape rather than volume. Weekdays without watches are all zero.
+    pub fn hour_profile_by_weekday(&self) -> [[f64; 24]; 7] {
+        self.count_watches_by_weekday_and_hour().map(|hours| {
+            let total = hours.iter().sum::<usize>();
+            hours.map(|count| {
+                if total == 0 {
+                    0.0
+                } else {
+                    count as f64 / total as f64
                 }
+            })
+        })
+    }
 
-                return self
-                    .videos
-                    .iter()
-                    .find(|(_, video)| matcher.eq(*video))
-                    .map(|(_, video)| video.clone());
-            }
-            WhereVideo::Reference(video) => Some(video),
-            WhereVideo::Any => self.videos.values().next().map(|video| video.clone()),
+    /// Returns a count of watches per period, keyed by the period's label (see
+    /// `Period::label`). Labels sort chronologically.
+    pub fn count_watches_by_period(&self, period: Period) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+
+        for watched in self.watches.iter() {
+            *counts
+                .entry(period.label(watched.local_date()))
+                .or_insert(0) += 1;
         }
+
+        counts
     }
 
-    pub fn find_or_create_channel(&mut self, url: &String, name: &String) -> Rc<Channel> {
-        if let Some(channel) = self.find_channel(WhereChannel::Structure(ChannelMatcher {
-            url: Some(&url),
-            name: Some(&name),
-        })) {
-            return channel;
+    /// Counts watches by how long after its upload the video was watched, in
+    /// `VideoAge::ALL` order. `upload_dates` is keyed by video ID or URL, the
+    /// watches of videos missing from it are counted separately.
+    pub fn count_watches_by_video_age(
+        &self,
+        upload_dates: &HashMap<String, NaiveDate>,
+    ) -> ([usize; 4], usize) {
+        let mut counts = [0; 4];
+        let mut unknown = 0;
+
+        for watched in self.watches.iter() {
+            let video = &watched.video;
+            let uploaded = upload_dates
+                .get(video.id_or_url())
+                .or_else(|| upload_dates.get(&video.url));
+            match uploaded {
+                Some(uploaded) => {
+                    let days = (watched.local_date() - *uploaded).num_days();
+                    counts[VideoAge::of_days(days) as usize] += 1;
+                }
+                None => unknown += 1,
+            }
         }
 
-        self.insert_channel(url.clone(), name.clone())
+        (counts, unknown)
     }
 
-    pub fn find_or_create_video(
-        &mut self,
-        url: String,
-        title: String,
-        channel: Rc<Channel>,
-    ) -> Rc<Video> {
-        if let Some(video) = self.videos.get(&url) {
-            return video.clone();
+    /// Adds up the durations of the watched videos in seconds, as if each was
+    /// watched to the end. `durations` is keyed by video ID or URL like
+    /// `upload_dates`, the watches of videos missing from it are counted
+    /// separately.
+    pub fn sum_watch_time(&self, durations: &HashMap<String, u64>) -> (u64, usize) {
+        let mut seconds = 0;
+        let mut unknown = 0;
+
+        for watched in self.watches.iter() {
+            let video = &watched.video;
+            let duration = durations
+                .get(video.id_or_url())
+                .or_else(|| durations.get(&video.url));
+            match duration {
+                Some(duration) => seconds += duration,
+                None => unknown += 1,
+            }
         }
 
-        let channel = self.find_or_create_channel(&channel.url, &channel.name);
-        self.insert_video(url, title, WhereChannel::Reference(channel))
+        (seconds, unknown)
     }
 
-    pub fn to_string(&self) -> String {
-        let scalar_models = ScalarModels {
-            watches: self
-                .watches
-                .iter()
-                .map(|watched| ScalarWatched {
-                    video: watched.video.id().clone(),
-                    when: watched.when,
-                })
-                .collect(),
-            channels: self
-                .channels
-                .iter()
-                .map(|(_, channel)| ScalarChannel {
-                    url: channel.url.clone(),
-                    name: channel.name.clone(),
-                })
-                .collect(),
-            videos: self
-                .videos
-                .iter()
-                .map(|(_, video)| ScalarVideo {
-                    url: video.url.clone(),
-                    title: video.title.clone(),
-                    channel: video.channel.id().clone(),
-                })
-                .collect(),
+    /// Watches on every local date from the first watch to the last, including
+    /// days without any.
+    pub fn watches_per_day(&self) -> Vec<(NaiveDate, usize)> {
+        let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        for watched in self.watches.iter() {
+            *counts.entry(watched.local_date()).or_insert(0) += 1;
+        }
+
+        let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) else {
+            return Vec::new();
         };
 
-        serde_json::to_string(&scalar_models).unwrap()
+        first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .map(|date| (date, counts.get(&date).copied().unwrap_or(0)))
+            .collect()
     }
 
-    pub fn from_str(s: String) -> serde_json::Result<Models> {
-        let scalar_models: ScalarModels = serde_json::from_str(&s)?;
+    /// Days with more watches than `k` standard deviations above the daily
+    /// mean, oldest first, each with its most watched channel and that
+    /// channel's watches. Days without watches between the first and last
+    /// count towards the mean. Also returns the mean.
+    pub fn outlier_days(&self, k: f64) -> (f64, Vec<OutlierDay>) {
+        let days = self.watches_per_day();
+        if days.is_empty() {
+            return (0.0, Vec::new());
+        }
 
-        let mut models = Models {
-            watches: Vec::new(),
-            channels: HashMap::new(),
-            videos: HashMap::new(),
+        let counts = days.iter().map(|(_, count)| *count as f64);
+        let mean = counts.clone().sum::<f64>() / days.len() as f64;
+        let variance = counts.map(|count| (count - mean).powi(2)).sum::<f64>() / days.len() as f64;
+        let threshold = mean + k * variance.sqrt();
+
+        let mut by_day: HashMap<NaiveDate, HashMap<&String, (usize, Rc<Channel>)>> = HashMap::new();
+        for watched in self.watches.iter() {
+            let channel = &watched.video.channel;
+            by_day
+                .entry(watched.local_date())
+                .or_default()
+                .entry(channel.id())
+                .or_insert_with(|| (0, channel.clone()))
+                .0 += 1;
+        }
+
+        let outliers = days
+            .into_iter()
+            .filter(|(_, count)| *count as f64 > threshold)
+            .filter_map(|(date, count)| {
+                let mut channels = by_day.remove(&date)?.into_values().collect::<Vec<_>>();
+                rank_channels(&mut channels);
+                let top = channels.into_iter().next()?;
+                Some((date, count, top))
+            })
+            .collect();
+
+        (mean, outliers)
+    }
+
+    /// Removes watches for which `f` returns true, along with videos and
+    /// channels that no longer have any watches. Returns the number of watches
+    /// removed.
+    pub fn remove_watches_matching(&mut self, f: impl Fn(&Watched) -> bool) -> usize {
+        self.retain(|watched| !f(watched))
+    }
+
+    /// Keeps only the watches for which `f` returns true, dropping videos and
+    /// channels left without any. Returns the number of watches removed.
+    pub fn retain(&mut self, f: impl FnMut(&Watched) -> bool) -> usize {
+        let before = self.watches.len();
+        self.watches.retain(f);
+        self.prune_orphans();
+
+        before - self.watches.len()
+    }
+
+    /// Keeps a random `n` of the watches, in their original order, dropping
+    /// videos and channels left without any. The same `seed` keeps the same
+    /// watches. Returns the number of watches removed.
+    pub fn sample(&mut self, n: usize, seed: u64) -> usize {
+        let len = self.watches.len();
+        if n >= len {
+            return 0;
+        }
+
+        // The first `n` indices of a partial Fisher-Yates shuffle
+        let mut indices = (0..len).collect::<Vec<_>>();
+        let mut state = seed;
+        for i in 0..n {
+            let j = i + (splitmix64(&mut state) % (len - i) as u64) as usize;
+            indices.swap(i, j);
+        }
+
+        let mut keep = vec![false; len];
+        for &i in &indices[..n] {
+            keep[i] = true;
+        }
+
+        let mut keep = keep.into_iter();
+        self.retain(|_| keep.next().unwrap())
+    }
+
+    /// Removes watches of videos from channels `matcher` matches. Returns the
+    /// number of watches removed.
+    pub fn remove_watches_of_channel(&mut self, matcher: ChannelMatcher) -> usize {
+        self.remove_watches_matching(|watched| matcher.matches(&watched.video.channel))
+    }
+
+    /// Removes watches whose local date is before `since` or after `until`
+    /// (both inclusive). Returns the number of watches removed.
+    pub fn remove_watches_outside(
+        &mut self,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    ) -> usize {
+        self.remove_watches_matching(|watched| {
+            let date = watched.local_date();
+            since.is_some_and(|since| date < since) || until.is_some_and(|until| date > until)
+        })
+    }
+
+    /// Removes watches dated after `after`, see `count_future_watches`.
+    /// Returns the number of watches removed.
+    pub fn remove_future_watches(&mut self, after: chrono::DateTime<Utc>) -> usize {
+        self.remove_watches_matching(|watched| watched.when > after)
+    }
+
+    /// Drops videos and channels that aren't referenced by any watch. The
+    /// methods removing watches already do this. Returns the number of videos
+    /// and channels dropped.
+    pub fn prune_orphans(&mut self) -> usize {
+        let before = self.videos.len() + self.channels.len();
+
+        let mut watched_videos = HashMap::new();
+        for watched in self.watches.iter() {
+            watched_videos.insert(watched.video.id(), watched.video.channel.id());
+        }
+
+        self.videos
+            .retain(|url, _| watched_videos.contains_key(url));
+
+        let watched_channels = watched_videos.values().collect::<HashSet<_>>();
+        self.channels
+            .retain(|url, _| watched_channels.contains(&url));
+
+        before - self.videos.len() - self.channels.len()
+    }
+
+    /// Moves every watch to UTC, so its local date, hour and weekday are UTC's
+    /// instead of its own offset's. The instants don't change.
+    pub fn use_utc(&mut self) {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        for watched in self.watches.iter_mut() {
+            watched.when = watched.when.with_timezone(&utc);
+        }
+    }
+
+    /// Merges channels that YouTube listed under different URL forms
+    /// (`/channel/UC...`, `/@handle`, `/user/name`, `/c/name`). Channels are
+    /// merged when their names match and their handles don't disagree, which is
+    /// a heuristic. Different `UC...` IDs are never merged, and handles are only
+    /// matched to an ID when it's the only one with that name. The merged
+    /// channel keeps the `UC...` URL if there is one, then the `/@` URL.
+    /// Returns the number of channels merged away.
+    pub fn group_channels_by_handle(&mut self) -> usize {
+        let mut by_name: HashMap<&String, Vec<&Rc<Channel>>> = HashMap::new();
+        for channel in self.channels.values() {
+            by_name.entry(&channel.name).or_default().push(channel);
+        }
+
+        let mut canonical: HashMap<String, Rc<Channel>> = HashMap::new();
+        for channels in by_name.into_values() {
+            let mut by_id: BTreeMap<&str, Vec<&Rc<Channel>>> = BTreeMap::new();
+            let mut by_handle: BTreeMap<String, Vec<&Rc<Channel>>> = BTreeMap::new();
+            for channel in channels {
+                if let Some(id) = channel.channel_id() {
+                    by_id.entry(id).or_default().push(channel);
+                } else if let Some(handle) = channel.handle() {
+                    by_handle
+                        .entry(handle.to_lowercase())
+                        .or_default()
+                        .push(channel);
+                }
+            }
+
+            // A handle can only be matched to an ID when there's exactly one
+            // candidate on each side
+            if by_id.len() == 1 && by_handle.len() == 1 {
+                let (_, with_handle) = by_handle.pop_first().unwrap();
+                by_id.values_mut().next().unwrap().extend(with_handle);
+            }
+
+            let groups = by_id.into_values().chain(by_handle.into_values());
+            for group in groups {
+                let keep = group
+                    .iter()
+                    .min_by_key(|channel| {
+                        let rank = if channel.channel_id().is_some() {
+                            0
+                        } else if channel.url.contains("/@") {
+                            1
+                        } else {
+                            2
+                        };
+                        (rank, &channel.url)
+                    })
+                    .unwrap();
+
+                for channel in group.iter() {
+                    if channel.url != keep.url {
+                        canonical.insert(channel.url.clone(), (*keep).clone());
+                    }
+                }
+            }
+        }
+
+        for video in self.videos.values_mut() {
+            if let Some(channel) = canonical.get(&video.channel.url) {
+                *video = Rc::new(Video {
+                    url: video.url.clone(),
+                    title: video.title.clone(),
+                    channel: channel.clone(),
+                });
+            }
+        }
+        for watched in self.watches.iter_mut() {
+            if canonical.contains_key(&watched.video.channel.url) {
+                watched.video = self.videos[&watched.video.url].clone();
+            }
+        }
+        self.channels.retain(|url, _| !canonical.contains_key(url));
+
+        canonical.len()
+    }
+
+    /// Removes repeat watches of a video that happen within `window` of the
+    /// last counted watch of the same video, so a video looped in the
+    /// background counts once per window. Returns the number of watches
+    /// removed.
+    pub fn collapse_repeats(&mut self, window: chrono::Duration) -> usize {
+        let mut order = (0..self.watches.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| self.watches[*i].when);
+
+        let mut keep = vec![true; self.watches.len()];
+        let mut last_counted: HashMap<&String, chrono::DateTime<FixedOffset>> = HashMap::new();
+
+        for i in order {
+            let watched = &self.watches[i];
+
+            match last_counted.get(watched.video.id()) {
+                Some(last) if watched.when - *last < window => keep[i] = false,
+                _ => {
+                    last_counted.insert(watched.video.id(), watched.when);
+                }
+            }
+        }
+
+        let before = self.watches.len();
+        let mut keep = keep.into_iter();
+        self.watches.retain(|_| keep.next().unwrap());
+
+        before - self.watches.len()
+    }
+
+    /// Whether there are no watches, like for a history that was just
+    /// cleared.
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn insert_watched(
+        &mut self,
+        when: chrono::DateTime<FixedOffset>,
+        video: WhereVideo,
+    ) -> Watched {
+        let video = self.find_video(video).unwrap().clone();
+        let watched = Watched {
+            video,
+            when,
+            offset_ambiguous: false,
         };
+        self.watches.push(watched.clone());
+
+        watched
+    }
+
+    /// Like `insert_watched`, for a watch whose offset was guessed.
+    pub fn insert_watched_ambiguous_offset(
+        &mut self,
+        when: chrono::DateTime<FixedOffset>,
+        video: WhereVideo,
+    ) -> Watched {
+        let mut watched = self.insert_watched(when, video);
+        watched.offset_ambiguous = true;
+        self.watches.last_mut().unwrap().offset_ambiguous = true;
+
+        watched
+    }
+
+    /// Builds models from `(video url, title, channel name, channel url, when)`
+    /// rows in file order, creating each video and channel the first time it
+    /// appears, the same way the parsers do.
+    #[cfg(test)]
+    pub fn from_watches(rows: &[WatchRow]) -> Models {
+        let mut models = Models::new();
+
+        for (url, title, channel_name, channel_url, when) in rows {
+            let channel =
+                models.find_or_create_channel(&channel_url.to_string(), &channel_name.to_string());
+            let video = models.find_or_create_video(url.to_string(), title.to_string(), channel);
+            models.insert_watched(*when, WhereVideo::Reference(video));
+        }
+
+        models
+    }
+
+    /// The fraction of all watches, from 0 to 1, that come from the `k` most
+    /// watched channels, for each `k` in `ks`. An empty history has a share of
+    /// 0.
+    pub fn channel_concentration(&self, ks: &[usize]) -> Vec<(usize, f64)> {
+        let mut counts = self
+            .count_watched_by_channel()
+            .into_values()
+            .map(|(count, _)| count)
+            .collect::<Vec<_>>();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+
+        let total = self.watches.len().max(1) as f64;
+        ks.iter()
+            .map(|&k| {
+                let top = counts.iter().take(k).sum::<usize>();
+                (k, top as f64 / total)
+            })
+            .collect()
+    }
+
+    /// Counts watches on weekdays and on the `weekend` days, in that order, by
+    /// local date.
+    pub fn weekend_weekday_split(&self, weekend: &[Weekday]) -> (usize, usize) {
+        let weekend = self
+            .watches
+            .iter()
+            .filter(|watched| watched.is_weekend(weekend))
+            .count();
+
+        (self.watches.len() - weekend, weekend)
+    }
+
+    /// Like `weekend_weekday_split`, for each channel, keyed by channel ID.
+    pub fn weekend_weekday_split_by_channel(
+        &self,
+        weekend: &[Weekday],
+    ) -> HashMap<String, (usize, usize)> {
+        let mut counts = HashMap::new();
+
+        for watched in self.watches.iter() {
+            let count: &mut (usize, usize) = counts
+                .entry(watched.video.channel.id().clone())
+                .or_default();
+
+            if watched.is_weekend(weekend) {
+                count.1 += 1;
+            } else {
+                count.0 += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// The fraction of watches with a confidently known offset, from 0 to 1.
+    /// An empty history has full coverage.
+    pub fn timezone_coverage(&self) -> f64 {
+        if self.watches.is_empty() {
+            return 1.0;
+        }
+
+        let known = self
+            .watches
+            .iter()
+            .filter(|watched| !watched.offset_ambiguous)
+            .count();
+
+        known as f64 / self.watches.len() as f64
+    }
+
+    pub fn insert_channel(&mut self, url: String, name: String) -> Rc<Channel> {
+        let channel = Rc::new(Channel { url, name });
+        self.channels.insert(channel.id().clone(), channel.clone());
+
+        channel
+    }
+
+    pub fn insert_video(&mut self, url: String, title: String, channel: WhereChannel) -> Rc<Video> {
+        let video = Rc::new(Video {
+            url,
+            title,
+            channel: self.find_channel(channel).unwrap().clone(),
+        });
+
+        self.videos.insert(video.id().clone(), video.clone());
+
+        video
+    }
+
+    pub fn find_channel(&self, where_channel: WhereChannel) -> Option<Rc<Channel>> {
+        match where_channel {
+            WhereChannel::Structure(matcher) => {
+                if let Some(url) = matcher.url {
+                    return self.channels.get(url).cloned();
+                }
+
+                self.channels
+                    .iter()
+                    .find(|(_, channel)| matcher.matches(channel))
+                    .map(|(_, channel)| channel.clone())
+            }
+            WhereChannel::Reference(channel) => Some(channel),
+            WhereChannel::Any => self.channels.values().next().cloned(),
+        }
+    }
+
+    pub fn find_video(&self, where_video: WhereVideo<'_>) -> Option<Rc<Video>> {
+        match where_video {
+            WhereVideo::Structure(matcher) => {
+                if let Some(url) = matcher.url {
+                    return self.videos.get(url).cloned();
+                }
+
+                self.videos
+                    .iter()
+                    .find(|(_, video)| matcher.eq(*video))
+                    .map(|(_, video)| video.clone())
+            }
+            WhereVideo::Reference(video) => Some(video),
+            WhereVideo::Any => self.videos.values().next().cloned(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn channel_for_video(&self, where_video: WhereVideo) -> Option<Rc<Channel>> {
+        self.find_video(where_video)
+            .map(|video| video.channel.clone())
+    }
+
+    pub fn find_or_create_channel(&mut self, url: &String, name: &String) -> Rc<Channel> {
+        let id;
+        let (url, name) = if self.compact {
+            id = channel_id_from_url(url).unwrap_or(url).to_string();
+            (&id, &id)
+        } else {
+            (url, name)
+        };
+
+        if let Some(channel) = self.find_channel(WhereChannel::Structure(ChannelMatcher {
+            url: Some(url),
+            name: Some(name),
+            ignore_case: false,
+        })) {
+            return channel;
+        }
+
+        self.insert_channel(url.clone(), name.clone())
+    }
+
+    pub fn find_or_create_video(
+        &mut self,
+        url: String,
+        title: String,
+        channel: Rc<Channel>,
+    ) -> Rc<Video> {
+        let (url, title) = if self.compact {
+            let id = video_id_from_url(&url).unwrap_or(&url).to_string();
+            (id.clone(), id)
+        } else if title.trim().is_empty() {
+            // Usually a malformed row, show something recognizable instead of
+            // a blank line
+            self.empty_titles += 1;
+            let title = format!("(untitled {})", video_id_from_url(&url).unwrap_or(&url));
+            (url, title)
+        } else {
+            (url, title)
+        };
+
+        if let Some(video) = self.videos.get(&url) {
+            if video.channel.url == channel.url {
+                return video.clone();
+            }
+
+            self.channel_conflicts.insert(url.clone());
+
+            match self.channel_conflict_policy {
+                ChannelConflictPolicy::First => return video.clone(),
+                ChannelConflictPolicy::Last => {
+                    // Earlier watches keep the replaced video until
+                    // resolve_channel_conflicts, rather than being searched
+                    // for on every conflict
+                    self.replaced_channels.insert(video.channel.url.clone());
+                    let channel = self.find_or_create_channel(&channel.url, &channel.name);
+                    return self.insert_video(url, title, WhereChannel::Reference(channel));
+                }
+            }
+        }
+
+        let channel = self.find_or_create_channel(&channel.url, &channel.name);
+        self.insert_video(url, title, WhereChannel::Reference(channel))
+    }
+
+    /// Points watches of videos replaced under `ChannelConflictPolicy::Last`
+    /// at the replacement, and removes the channels left without videos.
+    /// Parsers call this once every row is inserted.
+    pub fn resolve_channel_conflicts(&mut self) {
+        if self.replaced_channels.is_empty() {
+            return;
+        }
+
+        for watched in self.watches.iter_mut() {
+            if let Some(video) = self.videos.get(&watched.video.url) {
+                if !Rc::ptr_eq(video, &watched.video) {
+                    watched.video = video.clone();
+                }
+            }
+        }
+
+        let replaced = std::mem::take(&mut self.replaced_channels);
+        let used = self
+            .videos
+            .values()
+            .map(|video| &video.channel.url)
+            .collect::<HashSet<_>>();
+        self.channels
+            .retain(|url, _| !replaced.contains(url) || used.contains(url));
+    }
+
+    fn to_scalar(&self) -> ScalarModels {
+        let mut channels = self
+            .channels
+            .values()
+            .map(|channel| ScalarChannel {
+                url: channel.url.clone(),
+                name: channel.name.clone(),
+            })
+            .collect::<Vec<_>>();
+        channels.sort_by(|a, b| a.url.cmp(&b.url));
+
+        let mut videos = self
+            .videos
+            .values()
+            .map(|video| ScalarVideo {
+                url: video.url.clone(),
+                title: video.title.clone(),
+                channel: video.channel.id().clone(),
+            })
+            .collect::<Vec<_>>();
+        videos.sort_by(|a, b| a.url.cmp(&b.url));
+
+        ScalarModels {
+            version: CACHE_VERSION,
+            watches: self
+                .watches
+                .iter()
+                .map(|watched| ScalarWatched {
+                    video: watched.video.id().clone(),
+                    when: watched.when,
+                    offset_ambiguous: watched.offset_ambiguous,
+                })
+                .collect(),
+            channels,
+            videos,
+        }
+    }
+
+    /// Writes the cache format (see `CACHE_VERSION`) to `writer` without
+    /// building the whole string first. `to_string` gives the same output.
+    pub fn to_writer(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+        serde_json::to_writer(writer, &self.to_scalar())?;
+        Ok(())
+    }
+
+    /// The cache format (see `CACHE_VERSION`) as a JSON value, for callers
+    /// that want the data without going through a file.
+    #[cfg(test)]
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(self.to_scalar()).unwrap()
+    }
+
+    /// Reads the cache format from a string. Kept for callers that already
+    /// have one, `from_reader` avoids loading the whole file.
+    #[cfg(test)]
+    pub fn from_str(s: String) -> serde_json::Result<Models> {
+        Self::from_scalar(serde_json::from_str(&s)?)
+    }
+
+    /// Reads the cache format from `reader` without holding the whole file in
+    /// memory. Wrap files in a `BufReader`, `reader` is read in small pieces.
+    pub fn from_reader(reader: impl std::io::Read) -> serde_json::Result<Models> {
+        Self::from_scalar(serde_json::from_reader(reader)?)
+    }
+
+    fn from_scalar(scalar_models: ScalarModels) -> serde_json::Result<Models> {
+        if scalar_models.version != CACHE_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported cache version {}, expected {}",
+                scalar_models.version, CACHE_VERSION
+            )));
+        }
+
+        let mut models = Models::new();
 
         for channel in scalar_models.channels {
             let channel = Channel {
@@ -327,7 +1443,9 @@ impl Models {
         }
 
         for video in scalar_models.videos {
-            let channel = &models.channels.get(&video.channel).unwrap();
+            let channel = models.channels.get(&video.channel).ok_or_else(|| {
+                serde::de::Error::custom(format!("unknown channel {}", video.channel))
+            })?;
             let video = Video {
                 url: video.url,
                 title: video.title,
@@ -337,10 +1455,13 @@ impl Models {
         }
 
         for watched in scalar_models.watches {
-            let video = &models.videos.get(&watched.video).unwrap();
+            let video = models.videos.get(&watched.video).ok_or_else(|| {
+                serde::de::Error::custom(format!("unknown video {}", watched.video))
+            })?;
             let watched = Watched {
                 video: (*video).clone(),
                 when: watched.when,
+                offset_ambiguous: watched.offset_ambiguous,
             };
             models.watches.push(watched);
         }
@@ -349,12 +1470,258 @@ impl Models {
     }
 }
 
+/// A channel's watch count, with the watch counts of its top videos.
+pub type ChannelVideoCounts = (usize, Rc<Channel>, Vec<(usize, Rc<Video>)>);
+
+/// A watch for `Models::from_watches`: video URL, title, channel name,
+/// channel URL and time.
+#[cfg(test)]
+pub type WatchRow<'a> = (
+    &'a str,
+    &'a str,
+    &'a str,
+    &'a str,
+    chrono::DateTime<FixedOffset>,
+);
+
+/// A day's date and watch count, with its most watched channel's count.
+pub type OutlierDay = (NaiveDate, usize, (usize, Rc<Channel>));
+
+/// Values of `a` whose key is (or with `present` false, isn't) in `b`, sorted
+/// by key.
+fn intersect<T>(
+    a: &HashMap<String, Rc<T>>,
+    b: &HashMap<String, Rc<T>>,
+    present: bool,
+) -> Vec<Rc<T>> {
+    let mut matches = a
+        .iter()
+        .filter(|(key, _)| b.contains_key(*key) == present)
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|(key, _)| *key);
+
+    matches
+        .into_iter()
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+/// A small, seedable random number generator, so sampling doesn't need a
+/// dependency. See https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Sorts video counts most watched first. Ties are broken by title, then URL,
+/// so rankings are stable between runs.
+pub fn rank_videos(counts: &mut [(usize, Rc<Video>)]) {
+    counts.sort_by(|(a_count, a), (b_count, b)| {
+        b_count
+            .cmp(a_count)
+            .then_with(|| a.title.cmp(&b.title))
+            .then_with(|| a.url.cmp(&b.url))
+    });
+}
+
+/// Sorts channel counts most watched first. Ties are broken by name, then URL,
+/// so rankings are stable between runs.
+pub fn rank_channels(counts: &mut [(usize, Rc<Channel>)]) {
+    counts.sort_by(|(a_count, a), (b_count, b)| {
+        b_count
+            .cmp(a_count)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.url.cmp(&b.url))
+    });
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Period {
+    /// Formats the period containing `date`. Weeks are ISO weeks, so the year
+    /// of a week can differ from the calendar year near January 1st.
+    ///
+    /// Day: 2023-06-04
+    /// Week: 2023-W22
+    /// Month: 2023-06
+    /// Quarter: 2023-Q2
+    /// Year: 2023
+    pub fn label(&self, date: NaiveDate) -> String {
+        match self {
+            Period::Day => date.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => date.format("%Y-%m").to_string(),
+            Period::Quarter => format!("{}-Q{}", date.year(), date.month0() / 3 + 1),
+            Period::Year => date.year().to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Period {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            "quarter" => Ok(Period::Quarter),
+            "year" => Ok(Period::Year),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How long after its upload a video was watched.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoAge {
+    SameDay,
+    UnderWeek,
+    UnderMonth,
+    Older,
+}
+
+impl VideoAge {
+    pub const ALL: [VideoAge; 4] = [
+        VideoAge::SameDay,
+        VideoAge::UnderWeek,
+        VideoAge::UnderMonth,
+        VideoAge::Older,
+    ];
+
+    /// The bucket for a watch `days` after the upload date. Watches dated
+    /// before the upload, like premieres in another timezone, count as the
+    /// same day.
+    pub fn of_days(days: i64) -> VideoAge {
+        match days {
+            ..=0 => VideoAge::SameDay,
+            1..=6 => VideoAge::UnderWeek,
+            7..=29 => VideoAge::UnderMonth,
+            _ => VideoAge::Older,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VideoAge::SameDay => "Same day",
+            VideoAge::UnderWeek => "Under a week",
+            VideoAge::UnderMonth => "Under a month",
+            VideoAge::Older => "Older",
+        }
+    }
+}
+
+/// A calendar day without a year, like 06-04.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MonthDay {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl MonthDay {
+    pub fn of(date: NaiveDate) -> MonthDay {
+        MonthDay {
+            month: date.month(),
+            day: date.day(),
+        }
+    }
+}
+
+impl std::str::FromStr for MonthDay {
+    type Err = ();
+
+    /// Parses `MM-DD`. February 29th is allowed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A leap year, so any valid day parses
+        let date = NaiveDate::parse_from_str(&format!("2000-{}", s), "%Y-%m-%d").map_err(|_| ())?;
+        Ok(MonthDay::of(date))
+    }
+}
+
+/// Models are equal when they hold the same channels, videos and watches, in
+/// any order.
+impl PartialEq for Models {
+    fn eq(&self, other: &Self) -> bool {
+        fn channels(models: &Models) -> Vec<(&str, &str)> {
+            let mut channels = models
+                .channels
+                .values()
+                .map(|channel| (channel.url.as_str(), channel.name.as_str()))
+                .collect::<Vec<_>>();
+            channels.sort_unstable();
+            channels
+        }
+
+        fn videos(models: &Models) -> Vec<(&str, &str, &str)> {
+            let mut videos = models
+                .videos
+                .values()
+                .map(|video| {
+                    (
+                        video.url.as_str(),
+                        video.title.as_str(),
+                        video.channel.url.as_str(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            videos.sort_unstable();
+            videos
+        }
+
+        fn watches(models: &Models) -> Vec<(&str, chrono::DateTime<FixedOffset>)> {
+            let mut watches = models
+                .watches
+                .iter()
+                .map(|watched| (watched.video.url.as_str(), watched.when))
+                .collect::<Vec<_>>();
+            watches.sort_unstable();
+            watches
+        }
+
+        channels(self) == channels(other)
+            && videos(self) == videos(other)
+            && watches(self) == watches(other)
+    }
+}
+
+impl std::fmt::Display for Models {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self.to_scalar()).unwrap())
+    }
+}
+
 pub struct ChannelMatcher<'a> {
     url: Option<&'a String>,
     name: Option<&'a String>,
+    /// Compare `name` case-insensitively.
+    ignore_case: bool,
 }
 
-impl ChannelMatcher<'_> {
+impl<'a> ChannelMatcher<'a> {
+    /// Matches channels named `name`, ignoring case.
+    pub fn name_ignoring_case(name: &'a String) -> Self {
+        Self {
+            url: None,
+            name: Some(name),
+            ignore_case: true,
+        }
+    }
+
     fn matches(&self, channel: &Rc<Channel>) -> bool {
         if let Some(url) = self.url {
             // If matching on primary key (url), other fields can be skipped
@@ -362,18 +1729,24 @@ impl ChannelMatcher<'_> {
         }
 
         if let Some(name) = self.name {
-            if channel.name != *name {
+            let matches = if self.ignore_case {
+                channel.name.to_lowercase() == name.to_lowercase()
+            } else {
+                channel.name == *name
+            };
+            if !matches {
                 return false;
             }
         }
 
-        return true;
+        true
     }
 }
 
 pub enum WhereChannel<'a> {
     Structure(ChannelMatcher<'a>),
     Reference(Rc<Channel>),
+    #[allow(dead_code)]
     Any,
 }
 
@@ -413,7 +1786,7 @@ impl PartialEq<Rc<Video>> for VideoMatcher<'_> {
             }
         }
 
-        return true;
+        true
     }
 }
 
@@ -447,14 +1820,10 @@ impl WhereVideo<'_> {
                     }
                 }
 
-                return true;
-            }
-            WhereVideo::Reference(reference) => {
-                return reference == &video;
-            }
-            WhereVideo::Any => {
-                return true;
+                true
             }
+            WhereVideo::Reference(reference) => reference == &video,
+            WhereVideo::Any => true,
         }
     }
 }
@@ -478,7 +1847,7 @@ impl PartialEq<Watched> for WatchedMatcher<'_> {
             }
         }
 
-        return true;
+        true
     }
 }
 
@@ -505,14 +1874,1370 @@ impl WhereWatched<'_> {
                     }
                 }
 
-                return true;
+                true
+            }
+            WhereWatched::Reference(reference) => **reference == watched,
+            WhereWatched::Any => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> chrono::DateTime<FixedOffset> {
+        chrono::DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[test]
+    fn test_collapse_repeats() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let song = models.insert_video(
+            "song".into(),
+            "Song".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+        let other = models.insert_video(
+            "other".into(),
+            "Other".into(),
+            WhereChannel::Reference(channel),
+        );
+
+        // Insert out of order, the way multiple inputs would be
+        models.insert_watched(
+            date("2023-01-01T10:20:00Z"),
+            WhereVideo::Reference(song.clone()),
+        );
+        models.insert_watched(
+            date("2023-01-01T10:00:00Z"),
+            WhereVideo::Reference(song.clone()),
+        );
+        models.insert_watched(date("2023-01-01T10:05:00Z"), WhereVideo::Reference(other));
+        models.insert_watched(
+            date("2023-01-01T10:45:00Z"),
+            WhereVideo::Reference(song.clone()),
+        );
+
+        let collapsed = models.collapse_repeats(chrono::Duration::minutes(30));
+
+        assert_eq!(collapsed, 1);
+        assert_eq!(models.count_watches(WhereWatched::Any), 3);
+        assert_eq!(models.count_watched_by_video()[&song.url].0, 2);
+    }
+
+    #[test]
+    fn test_local_date_and_hour() {
+        let video = Rc::new(Video {
+            url: "video".into(),
+            title: "Video".into(),
+            channel: Rc::new(Channel {
+                url: "channel".into(),
+                name: "Channel".into(),
+            }),
+        });
+        let watched = |when: &str| Watched {
+            video: video.clone(),
+            when: date(when),
+            offset_ambiguous: false,
+        };
+
+        // The same instant on either side of the end of daylight saving time
+        let edt = watched("2021-11-07T00:30:00-04:00");
+        let est = watched("2021-11-06T23:30:00-05:00");
+        assert_eq!(edt.when, est.when);
+        assert_eq!(
+            edt.local_date(),
+            NaiveDate::from_ymd_opt(2021, 11, 7).unwrap()
+        );
+        assert_eq!(edt.local_hour(), 0);
+        assert_eq!(
+            est.local_date(),
+            NaiveDate::from_ymd_opt(2021, 11, 6).unwrap()
+        );
+        assert_eq!(est.local_hour(), 23);
+
+        // 1:30 happens twice when clocks fall back
+        assert_eq!(watched("2021-11-07T01:30:00-04:00").local_hour(), 1);
+        assert_eq!(watched("2021-11-07T01:30:00-05:00").local_hour(), 1);
+    }
+
+    #[test]
+    fn test_identity_by_url() {
+        let channel = |name: &str| {
+            Rc::new(Channel {
+                url: "c".into(),
+                name: name.into(),
+            })
+        };
+        let video = |title: &str, channel: Rc<Channel>| {
+            Rc::new(Video {
+                url: "v".into(),
+                title: title.into(),
+                channel,
+            })
+        };
+
+        assert_eq!(channel("Old name"), channel("New name"));
+
+        let videos = [
+            video("Title", channel("Old name")),
+            video("Renamed", channel("New name")),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>();
+        assert_eq!(videos.len(), 1);
+    }
+
+    #[test]
+    fn test_watches_on_day() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+        let video = models.insert_video("v".into(), "V".into(), WhereChannel::Reference(channel));
+
+        for when in [
+            "2021-06-04T20:00:00Z",
+            "2023-06-04T09:00:00Z",
+            "2023-06-04T08:00:00Z",
+            "2023-06-05T01:00:00Z",
+            // June 4th in its own offset, but June 5th in UTC
+            "2022-06-04T22:00:00-04:00",
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        let days = models.watches_on_day("06-04".parse().unwrap());
+        let hours = days
+            .iter()
+            .map(|(year, watches)| {
+                let hours = watches.iter().map(|w| w.local_hour()).collect::<Vec<_>>();
+                (*year, hours)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hours,
+            [(2021, vec![20]), (2022, vec![22]), (2023, vec![8, 9])]
+        );
+
+        assert_eq!(
+            "02-29".parse::<MonthDay>(),
+            Ok(MonthDay { month: 2, day: 29 })
+        );
+        assert_eq!("02-30".parse::<MonthDay>(), Err(()));
+        assert_eq!("2023-06-04".parse::<MonthDay>(), Err(()));
+    }
+
+    #[test]
+    fn test_top_video_by_year() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+
+        for (url, title, when, watches) in [
+            ("1", "Zebra", "2022-03-01T10:00:00Z", 2),
+            ("2", "Apple", "2022-04-01T10:00:00Z", 2),
+            ("3", "Mango", "2022-05-01T10:00:00Z", 1),
+            ("3", "Mango", "2023-01-01T10:00:00Z", 3),
+            ("1", "Zebra", "2023-01-02T10:00:00Z", 1),
+        ] {
+            let video = models.insert_video(
+                url.into(),
+                title.into(),
+                WhereChannel::Reference(channel.clone()),
+            );
+            for _ in 0..watches {
+                models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+            }
+        }
+
+        let top = models
+            .top_video_by_year()
+            .into_iter()
+            .map(|(year, (count, video))| (year, count, video.title.clone()))
+            .collect::<Vec<_>>();
+        // The tie in 2022 goes to the first title
+        assert_eq!(
+            top,
+            [
+                (2022, 2, "Apple".to_string()),
+                (2023, 3, "Mango".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let build = || {
+            let mut models = Models::new();
+            let channel = models.insert_channel("c".into(), "C".into());
+            for i in 0..100 {
+                let video = models.insert_video(
+                    i.to_string(),
+                    i.to_string(),
+                    WhereChannel::Reference(channel.clone()),
+                );
+                let when = date("2023-01-01T10:00:00Z") + chrono::Duration::minutes(i);
+                models.insert_watched(when, WhereVideo::Reference(video));
+            }
+            models
+        };
+        let urls = |models: &Models| {
+            models
+                .watches()
+                .iter()
+                .map(|watched| watched.video.url.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut a = build();
+        assert_eq!(a.sample(10, 7), 90);
+        assert_eq!(a.watches().len(), 10);
+        assert_eq!(a.count_videos(WhereVideo::Any), 10);
+        assert!(a.watches().windows(2).all(|w| w[0].when < w[1].when));
+
+        // The same seed picks the same watches, another seed doesn't
+        let mut b = build();
+        b.sample(10, 7);
+        assert_eq!(urls(&a), urls(&b));
+        let mut c = build();
+        c.sample(10, 8);
+        assert_ne!(urls(&a), urls(&c));
+
+        assert_eq!(build().sample(100, 7), 0);
+    }
+
+    #[test]
+    fn test_count_watches_by_video_age() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+        let known = models.insert_video(
+            "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            "Known".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+        let unknown = models.insert_video(
+            "unknown".into(),
+            "Unknown".into(),
+            WhereChannel::Reference(channel),
+        );
+
+        for (when, video) in [
+            ("2023-01-01T23:00:00-05:00", &known),
+            ("2023-01-07T10:00:00Z", &known),
+            ("2023-01-08T10:00:00Z", &known),
+            ("2024-01-01T10:00:00Z", &known),
+            ("2023-01-01T10:00:00Z", &unknown),
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        // Keyed by ID, the first watch is on the upload date locally
+        let upload_dates = HashMap::from([(
+            "rtTWtzWav8I".to_string(),
+            NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+        )]);
+        assert_eq!(
+            models.count_watches_by_video_age(&upload_dates),
+            ([1, 1, 1, 1], 1)
+        );
+    }
+
+    #[test]
+    fn test_sum_watch_time() {
+        let known = "https://www.youtube.com/watch?v=rtTWtzWav8I";
+        let by_url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+        let models = Models::from_watches(&[
+            (known, "Known", "C", "c", date("2023-01-01T10:00:00Z")),
+            (known, "Known", "C", "c", date("2023-01-02T10:00:00Z")),
+            (by_url, "By URL", "C", "c", date("2023-01-03T10:00:00Z")),
+            ("unknown", "Unknown", "C", "c", date("2023-01-04T10:00:00Z")),
+        ]);
+
+        let durations =
+            HashMap::from([("rtTWtzWav8I".to_string(), 253), (by_url.to_string(), 212)]);
+        assert_eq!(models.sum_watch_time(&durations), (2 * 253 + 212, 1));
+    }
+
+    #[test]
+    fn test_all_sorted() {
+        let mut models = Models::new();
+        let b = models.insert_channel("b".into(), "B".into());
+        let a2 = models.insert_channel("a2".into(), "A".into());
+        let a1 = models.insert_channel("a1".into(), "A".into());
+        models.insert_video("2".into(), "Same".into(), WhereChannel::Reference(b));
+        models.insert_video("3".into(), "First".into(), WhereChannel::Reference(a2));
+        models.insert_video("1".into(), "Same".into(), WhereChannel::Reference(a1));
+
+        let channels = models.all_channels_sorted();
+        let channels = channels.iter().map(|c| c.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(channels, ["a1", "a2", "b"]);
+
+        let videos = models.all_videos_sorted();
+        let videos = videos.iter().map(|v| v.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(videos, ["3", "1", "2"]);
+    }
+
+    #[test]
+    fn test_prune_orphans() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let unwatched = models.insert_channel("c".into(), "C".into());
+        models.insert_video(
+            "v2".into(),
+            "Video".into(),
+            WhereChannel::Reference(unwatched),
+        );
+        assert_eq!(models.prune_orphans(), 2);
+
+        // Removing the only watch of a video prunes it and its channel
+        models.watches.retain(|watched| watched.video.url != "v0");
+        assert_eq!(models.prune_orphans(), 2);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+        assert!(models
+            .find_channel(WhereChannel::Any)
+            .is_some_and(|c| c.url == "b"));
+        assert_eq!(models.prune_orphans(), 0);
+    }
+
+    #[test]
+    fn test_outlier_days() {
+        let mut models = Models::new();
+        let a = models.insert_channel("a".into(), "A".into());
+        let b = models.insert_channel("b".into(), "B".into());
+        let video_a = models.insert_video("1".into(), "1".into(), WhereChannel::Reference(a));
+        let video_b = models.insert_video("2".into(), "2".into(), WhereChannel::Reference(b));
+
+        // One watch a day for ten days, with six more on the 5th
+        for day in 1..=10 {
+            let when = date(&format!("2023-01-{:02}T10:00:00Z", day));
+            models.insert_watched(when, WhereVideo::Reference(video_a.clone()));
+        }
+        for _ in 0..6 {
+            let when = date("2023-01-05T12:00:00Z");
+            models.insert_watched(when, WhereVideo::Reference(video_b.clone()));
+        }
+
+        let (mean, outliers) = models.outlier_days(2.0);
+        assert_eq!(mean, 1.6);
+        let outliers = outliers
+            .into_iter()
+            .map(|(date, count, (channel_count, channel))| {
+                (date.to_string(), count, channel_count, channel.name.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outliers,
+            [("2023-01-05".to_string(), 7, 6, "B".to_string())]
+        );
+
+        assert!(models.outlier_days(4.0).1.is_empty());
+    }
+
+    #[test]
+    fn test_use_utc() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let video = models.find_video(WhereVideo::Any).unwrap();
+        models.insert_watched(
+            date("2023-01-01T22:30:00-05:00"),
+            WhereVideo::Reference(video),
+        );
+
+        models.use_utc();
+
+        let watched = &models.watches()[1];
+        assert_eq!(watched.when, date("2023-01-02T03:30:00Z"));
+        assert_eq!(
+            watched.local_date(),
+            NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()
+        );
+        assert_eq!(watched.local_hour(), 3);
+    }
+
+    #[test]
+    fn test_watches_per_day() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let video = models.find_video(WhereVideo::Any).unwrap();
+        for when in ["2023-01-03T23:00:00-05:00", "2023-01-01T12:00:00Z"] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        let days = models
+            .watches_per_day()
+            .into_iter()
+            .map(|(date, count)| (date.to_string(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            days,
+            [
+                ("2023-01-01".to_string(), 2),
+                ("2023-01-02".to_string(), 0),
+                ("2023-01-03".to_string(), 1),
+            ]
+        );
+        assert!(Models::new().watches_per_day().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_titles() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+        for (url, title) in [
+            ("3", "Some  video"),
+            ("1", "Some Video"),
+            ("2", "Other"),
+            ("4", "Another"),
+            ("5", "another "),
+        ] {
+            models.insert_video(
+                url.into(),
+                title.into(),
+                WhereChannel::Reference(channel.clone()),
+            );
+        }
+
+        let groups = models
+            .duplicate_titles()
+            .into_iter()
+            .map(|videos| videos.iter().map(|v| v.url.clone()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(groups, [vec!["4", "5"], vec!["1", "3"]]);
+    }
+
+    #[test]
+    fn test_iter_by_channel() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let video_b = models.videos["v1"].clone();
+        models.insert_watched(date("2022-01-01T10:00:00Z"), WhereVideo::Reference(video_b));
+
+        let groups = models
+            .iter_by_channel()
+            .map(|(channel, watches)| {
+                let dates = watches.iter().map(|w| w.when.to_rfc3339());
+                (channel.name.clone(), dates.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                (
+                    "B".to_string(),
+                    vec![
+                        "2022-01-01T10:00:00+00:00".to_string(),
+                        "2023-01-01T10:00:00+00:00".to_string()
+                    ]
+                ),
+                (
+                    "A".to_string(),
+                    vec!["2023-01-01T10:00:00+00:00".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channel_discovery_dates() {
+        // Newest first, like an export
+        let models = Models::from_watches(&[
+            ("1", "1", "A", "a", date("2023-03-01T10:00:00Z")),
+            ("3", "3", "C", "c", date("2023-02-01T12:00:00Z")),
+            ("2", "2", "B", "b", date("2023-02-01T10:00:00Z")),
+            ("1", "1", "A", "a", date("2023-01-01T10:00:00Z")),
+        ]);
+
+        let dates = models
+            .channel_discovery_dates()
+            .into_iter()
+            .map(|(date, channel)| (date.to_string(), channel.name.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dates,
+            [
+                ("2023-01-01".to_string(), "A".to_string()),
+                ("2023-02-01".to_string(), "B".to_string()),
+                ("2023-02-01".to_string(), "C".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_channels_by_watches() {
+        let when = date("2023-01-01T10:00:00Z");
+        let mut rows = vec![("v1", "1", "A", "a", when)];
+        rows.extend([("v2", "2", "B", "b", when); 2]);
+        rows.extend([("v3", "3", "C", "c", when); 5]);
+        rows.extend([("v4", "4", "D", "d", when); 6]);
+        let models = Models::from_watches(&rows);
+
+        assert_eq!(models.count_channels_by_watches(&[1, 5]), [1, 2, 1]);
+        assert_eq!(models.count_channels_by_watches(&[2, 10]), [2, 2, 0]);
+        assert_eq!(models.count_channels_by_watches(&[]), [4]);
+        assert_eq!(Models::new().count_channels_by_watches(&[1]), [0, 0]);
+    }
+
+    #[test]
+    fn test_from_watches() {
+        let models = Models::from_watches(&[
+            ("v1", "First", "A", "a", date("2023-01-02T10:00:00Z")),
+            ("v2", "Second", "A", "a", date("2023-01-01T12:00:00Z")),
+            ("v1", "First", "A", "a", date("2023-01-01T10:00:00Z")),
+        ]);
+
+        assert_eq!(models.watches.len(), 3);
+        assert_eq!(models.videos.len(), 2);
+        assert_eq!(models.channels.len(), 1);
+        assert!(Rc::ptr_eq(
+            &models.watches[0].video,
+            &models.watches[2].video
+        ));
+        assert!(Rc::ptr_eq(
+            &models.videos["v2"].channel,
+            &models.videos["v1"].channel
+        ));
+        assert_eq!(models.videos["v2"].title, "Second");
+        assert_eq!(models.watches[1].when, date("2023-01-01T12:00:00Z"));
+    }
+
+    #[test]
+    fn test_weekend_weekday_split() {
+        let mut models = Models::new();
+        let a = models.insert_channel("a".into(), "A".into());
+        let b = models.insert_channel("b".into(), "B".into());
+        let video_a = models.insert_video("1".into(), "1".into(), WhereChannel::Reference(a));
+        let video_b = models.insert_video("2".into(), "2".into(), WhereChannel::Reference(b));
+
+        // Friday and Monday
+        models.insert_watched(
+            date("2023-06-02T12:00:00Z"),
+            WhereVideo::Reference(video_a.clone()),
+        );
+        models.insert_watched(
+            date("2023-06-05T12:00:00Z"),
+            WhereVideo::Reference(video_a.clone()),
+        );
+        // Saturday in UTC, but the local date is still Friday
+        models.insert_watched(
+            date("2023-06-02T23:00:00-04:00"),
+            WhereVideo::Reference(video_a),
+        );
+        // Saturday and Sunday
+        models.insert_watched(
+            date("2023-06-03T12:00:00Z"),
+            WhereVideo::Reference(video_b.clone()),
+        );
+        models.insert_watched(date("2023-06-04T12:00:00Z"), WhereVideo::Reference(video_b));
+
+        assert_eq!(models.weekend_weekday_split(&DEFAULT_WEEKEND), (3, 2));
+
+        let by_channel = models.weekend_weekday_split_by_channel(&DEFAULT_WEEKEND);
+        assert_eq!(by_channel["a"], (3, 0));
+        assert_eq!(by_channel["b"], (0, 2));
+
+        // A Friday and Saturday weekend
+        assert_eq!(
+            models.weekend_weekday_split(&[Weekday::Fri, Weekday::Sat]),
+            (2, 3)
+        );
+    }
+
+    #[test]
+    fn test_top_videos_and_channels() {
+        let mut models = Models::new();
+        let a = models.insert_channel("a".into(), "A".into());
+        let b = models.insert_channel("b".into(), "B".into());
+        let when = date("2023-01-01T10:00:00Z");
+
+        for (url, title, channel, watches) in [
+            ("1", "Zebra", &b, 2),
+            ("2", "Apple", &b, 2),
+            ("3", "Mango", &a, 3),
+            ("4", "Kiwi", &a, 1),
+        ] {
+            let video = models.insert_video(
+                url.into(),
+                title.into(),
+                WhereChannel::Reference(channel.clone()),
+            );
+            for _ in 0..watches {
+                models.insert_watched(when, WhereVideo::Reference(video.clone()));
             }
-            WhereWatched::Reference(reference) => {
-                return **reference == watched;
+        }
+
+        let titles = models
+            .top_videos(3)
+            .into_iter()
+            .map(|(count, video)| (count, video.title.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec![
+                (3, "Mango".to_string()),
+                (2, "Apple".to_string()),
+                (2, "Zebra".to_string())
+            ]
+        );
+
+        let names = models
+            .top_channels(10)
+            .into_iter()
+            .map(|(count, channel)| (count, channel.name.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![(4, "A".to_string()), (4, "B".to_string())]);
+    }
+
+    #[test]
+    fn test_watch_count() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let video =
+            models.find_or_create_video("v0".into(), "Video".into(), models.channels["a"].clone());
+        models.insert_watched(
+            date("2023-01-02T10:00:00Z"),
+            WhereVideo::Reference(video.clone()),
+        );
+
+        assert_eq!(models.watch_count(WhereVideo::Reference(video)), 2);
+        let url = "v1".to_string();
+        let matcher = VideoMatcher {
+            url: Some(&url),
+            title: None,
+            channel: None,
+        };
+        assert_eq!(models.watch_count(WhereVideo::Structure(matcher)), 1);
+        assert_eq!(models.watch_count(WhereVideo::Any), 3);
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = models_with_channels(&[("x", "X"), ("y", "Y")]);
+        let b = models_with_channels(&[("y", "Y"), ("z", "Z")]);
+
+        let urls = |channels: Vec<Rc<Channel>>| {
+            channels
+                .iter()
+                .map(|channel| channel.url.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls(a.common_channels(&b)), vec!["y"]);
+
+        let (videos, channels) = a.unique_to(&b);
+        assert_eq!(urls(channels), vec!["x"]);
+        // Both have v0 and v1, but under different channels
+        assert_eq!(videos.len(), 0);
+        assert_eq!(a.common_videos(&b).len(), 2);
+    }
+
+    #[test]
+    fn test_watches_of() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let channel = models.channels["a"].clone();
+        let video = models.find_or_create_video(
+            "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            "Video".into(),
+            channel,
+        );
+        models.insert_watched(
+            date("2023-01-03T10:00:00Z"),
+            WhereVideo::Reference(video.clone()),
+        );
+        models.insert_watched(date("2023-01-02T10:00:00Z"), WhereVideo::Reference(video));
+
+        let watches = models.watches_of("https://youtu.be/rtTWtzWav8I");
+        assert_eq!(watches.len(), 2);
+        assert_eq!(watches[0].when, date("2023-01-02T10:00:00Z"));
+        assert_eq!(models.watches_of("v0").len(), 1);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut models = Models::new();
+        models.set_compact(true);
+
+        let channel = models.find_or_create_channel(
+            &"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng".into(),
+            &"Benn Jordan".into(),
+        );
+        let video = models.find_or_create_video(
+            "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            "An Addictive Alternative To DAWs".into(),
+            channel,
+        );
+
+        assert_eq!(video.url, "rtTWtzWav8I");
+        assert_eq!(video.title, "rtTWtzWav8I");
+        assert_eq!(video.channel.name, "UCshObcm-nLhbu8MY50EZ5Ng");
+    }
+
+    #[test]
+    fn test_watches_of_channel() {
+        let models = models_with_channels(&[
+            ("https://www.youtube.com/@Foo", "Foo"),
+            ("https://www.youtube.com/channel/UCbar", "Bar"),
+        ]);
+
+        assert_eq!(models.watches_of_channel("@foo").len(), 1);
+        assert_eq!(models.watches_of_channel("UCbar").len(), 1);
+        assert_eq!(models.watches_of_channel("Bar").len(), 1);
+        assert_eq!(models.watches_of_channel("Baz").len(), 0);
+    }
+
+    #[test]
+    fn test_top_videos_per_channel() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let channel = models.channels["b"].clone();
+        for (url, watches) in [("b1", 3), ("b2", 2)] {
+            let video = models.find_or_create_video(url.into(), url.into(), channel.clone());
+            for _ in 0..watches {
+                models.insert_watched(
+                    date("2023-01-01T10:00:00Z"),
+                    WhereVideo::Reference(video.clone()),
+                );
             }
-            WhereWatched::Any => {
-                return true;
+        }
+
+        let nested = models.top_videos_per_channel(1, 2);
+        assert_eq!(nested.len(), 1);
+        let (count, channel, videos) = &nested[0];
+        assert_eq!((*count, channel.url.as_str()), (6, "b"));
+        let urls = videos
+            .iter()
+            .map(|(count, video)| (*count, video.url.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(urls, vec![(3, "b1"), (2, "b2")]);
+    }
+
+    #[test]
+    fn test_empty_title() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let channel = models.channels["a"].clone();
+        let video = models.find_or_create_video(
+            "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            " ".into(),
+            channel,
+        );
+
+        assert_eq!(video.title, "(untitled rtTWtzWav8I)");
+        assert_eq!(models.empty_titles(), 1);
+    }
+
+    #[test]
+    fn test_hour_profile_by_weekday() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let video = models.find_video(WhereVideo::Any).unwrap();
+        // 2023-01-02 is a Monday, the last watch is Tuesday 00:30 in UTC but
+        // Monday 19:30 locally
+        for when in [
+            "2023-01-02T10:00:00-05:00",
+            "2023-01-02T10:30:00-05:00",
+            "2023-01-02T19:30:00-05:00",
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        let counts = models.count_watches_by_weekday_and_hour();
+        assert_eq!(counts[0][10], 2);
+        assert_eq!(counts[0][19], 1);
+
+        let profile = models.hour_profile_by_weekday();
+        assert_eq!(profile[0][10], 2.0 / 3.0);
+        assert_eq!(profile[0][19], 1.0 / 3.0);
+        assert_eq!(profile[1].iter().sum::<f64>(), 0.0);
+    }
+
+    #[test]
+    fn test_unique_by_year() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let video = models.videos["v0"].clone();
+        models.insert_watched(
+            date("2023-06-01T10:00:00Z"),
+            WhereVideo::Reference(video.clone()),
+        );
+        models.insert_watched(date("2024-01-01T10:00:00Z"), WhereVideo::Reference(video));
+
+        assert_eq!(
+            models.unique_videos_by_year(),
+            BTreeMap::from([(2023, 2), (2024, 1)])
+        );
+        assert_eq!(
+            models.unique_channels_by_year(),
+            BTreeMap::from([(2023, 2), (2024, 1)])
+        );
+    }
+
+    #[test]
+    fn test_channel_concentration() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B"), ("c", "C")]);
+        let video = models.videos["v0"].clone();
+        models.insert_watched(date("2023-01-02T10:00:00Z"), WhereVideo::Reference(video));
+
+        assert_eq!(
+            models.channel_concentration(&[1, 2, 10]),
+            [(1, 0.5), (2, 0.75), (10, 1.0)]
+        );
+        assert_eq!(Models::new().channel_concentration(&[1]), [(1, 0.0)]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut models = models_with_channels(&[("a", "A"), ("b", "B"), ("c", "C")]);
+
+        let removed = models.retain(|watched| watched.video.channel.url != "b");
+
+        assert_eq!(removed, 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 2);
+        assert_eq!(models.channels.len(), 2);
+        assert!(!models.channels.contains_key("b"));
+    }
+
+    #[test]
+    fn test_remove_watches_of_channel() {
+        let mut models = models_with_channels(&[("a", "Some Channel"), ("b", "Another")]);
+
+        let name = "some CHANNEL".to_string();
+        let removed = models.remove_watches_of_channel(ChannelMatcher::name_ignoring_case(&name));
+
+        assert_eq!(removed, 1);
+        assert_eq!(models.watches().len(), 1);
+        assert_eq!(models.watches()[0].video.channel.name, "Another");
+    }
+
+    #[test]
+    fn test_remove_watches_outside() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let video = models.find_video(WhereVideo::Any).unwrap();
+        for when in [
+            "2022-12-31T23:00:00-05:00",
+            "2023-01-31T23:00:00-05:00",
+            "2023-02-01T00:00:00-05:00",
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        let day = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        // The first watch, 2023-01-01T10:00:00Z, is also inside the range
+        assert_eq!(
+            models.remove_watches_outside(Some(day("2023-01-01")), Some(day("2023-01-31"))),
+            2
+        );
+        assert_eq!(models.watches().len(), 2);
+    }
+
+    #[test]
+    fn test_future_watches() {
+        let mut models = models_with_channels(&[("a", "A")]);
+        let video = models.find_video(WhereVideo::Any).unwrap();
+        for when in ["2023-06-01T23:00:00-05:00", "2023-06-02T12:00:00+09:00"] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        // 2023-06-02T04:00:00Z is after, 2023-06-02T03:00:00Z is not
+        let now = date("2023-06-02T03:30:00Z").with_timezone(&Utc);
+        assert_eq!(models.count_future_watches(now), 1);
+        assert_eq!(models.remove_future_watches(now), 1);
+        assert_eq!(models.watches().len(), 2);
+        assert_eq!(models.count_future_watches(now), 0);
+    }
+
+    #[test]
+    fn test_rank_ties_by_url() {
+        let channel = Rc::new(Channel {
+            url: "c".into(),
+            name: "C".into(),
+        });
+        let video = |url: &str| {
+            Rc::new(Video {
+                url: url.into(),
+                title: "Same".into(),
+                channel: channel.clone(),
+            })
+        };
+
+        let mut counts = vec![(1, video("b")), (1, video("a")), (2, video("c"))];
+        rank_videos(&mut counts);
+
+        let urls = counts
+            .iter()
+            .map(|(_, video)| video.url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+        let video = models.insert_video("v".into(), "V".into(), WhereChannel::Reference(channel));
+        models.insert_watched(
+            date("2023-01-01T10:00:00-05:00"),
+            WhereVideo::Reference(video),
+        );
+
+        let value = models.to_json_value();
+        assert_eq!(value["version"], CACHE_VERSION);
+        assert_eq!(value["videos"][0]["channel"], "c");
+
+        let loaded = Models::from_str(models.to_string()).unwrap();
+        assert_eq!(loaded.watches(), models.watches());
+
+        let mut written = Vec::new();
+        models.to_writer(&mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written.clone()).unwrap(),
+            models.to_string()
+        );
+        assert_eq!(Models::from_reader(written.as_slice()).unwrap(), models);
+
+        let old = r#"{"watches":[],"channels":[],"videos":[]}"#;
+        assert!(Models::from_str(old.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_cache_when_offsets() {
+        let whens = [
+            "2023-01-01T10:00:00Z",
+            "2023-01-01T10:00:00-05:00",
+            "2023-01-01T10:00:00+05:45",
+            "2023-01-01T10:00:00.250+14:00",
+            "2023-01-01T10:00:00-12:00",
+        ];
+        let rows = whens.map(|when| ("v", "V", "C", "c", date(when)));
+        let models = Models::from_watches(&rows);
+
+        let value = models.to_json_value();
+        assert_eq!(value["watches"][0]["when"], "2023-01-01T10:00:00+00:00");
+        assert_eq!(value["watches"][3]["when"], "2023-01-01T10:00:00.250+14:00");
+
+        let loaded = Models::from_str(models.to_string()).unwrap();
+        for (loaded, when) in loaded.watches.iter().zip(whens) {
+            assert_eq!(loaded.when, date(when));
+            assert_eq!(loaded.when.offset(), date(when).offset());
+        }
+
+        let bad = value.to_string().replace("+05:45", " +0545");
+        assert!(Models::from_str(bad).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_many() {
+        // A small linear congruential generator, so the models are varied but
+        // the same every run
+        let mut seed: u64 = 42;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+
+        let mut models = Models::new();
+        let channels = (0..5)
+            .map(|i| models.insert_channel(format!("c{i}"), format!("Channel \"{i}\"")))
+            .collect::<Vec<_>>();
+        let videos = (0..20)
+            .map(|i| {
+                let channel = channels[next(5) as usize].clone();
+                models.insert_video(
+                    format!("v{i}"),
+                    format!("Video <{i}> & more"),
+                    WhereChannel::Reference(channel),
+                )
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..200 {
+            let video = WhereVideo::Reference(videos[next(20) as usize].clone());
+            let offset = FixedOffset::east_opt(next(24) as i32 * 3600 - 11 * 3600).unwrap();
+            let when =
+                date("2023-01-01T00:00:00Z") + chrono::Duration::minutes(next(525600) as i64);
+            let when = when.with_timezone(&offset);
+            if next(2) == 0 {
+                models.insert_watched(when, video);
+            } else {
+                models.insert_watched_ambiguous_offset(when, video);
             }
         }
+
+        let loaded = Models::from_str(models.to_string()).unwrap();
+
+        assert_eq!(loaded, models);
+        assert_eq!(loaded.channels.len(), models.channels.len());
+        assert_eq!(
+            loaded.count_videos(WhereVideo::Any),
+            models.count_videos(WhereVideo::Any)
+        );
+        assert_eq!(loaded.watches(), models.watches());
+        assert_eq!(loaded.timezone_coverage(), models.timezone_coverage());
+        assert_eq!(loaded.top_videos(10), models.top_videos(10));
+        assert_eq!(loaded.top_channels(5), models.top_channels(5));
+    }
+
+    #[test]
+    fn test_eq_ignores_order() {
+        let mut a = models_with_channels(&[("a", "A"), ("b", "B")]);
+        let b = models_with_channels(&[("a", "A"), ("b", "B")]);
+        a.watches.reverse();
+        assert_eq!(a, b);
+
+        let video = a.find_video(WhereVideo::Any).unwrap();
+        a.insert_watched(date("2023-01-01T10:00:00Z"), WhereVideo::Reference(video));
+        assert_ne!(a, b);
+
+        let renamed = models_with_channels(&[("a", "A"), ("b", "Renamed")]);
+        assert_ne!(renamed, b);
+    }
+
+    fn models_with_channels(urls: &[(&str, &str)]) -> Models {
+        let mut models = Models::new();
+        let when = date("2023-01-01T10:00:00Z");
+
+        for (i, (url, name)) in urls.iter().enumerate() {
+            let channel = models.insert_channel(url.to_string(), name.to_string());
+            let video = models.insert_video(
+                format!("v{}", i),
+                "Video".into(),
+                WhereChannel::Reference(channel),
+            );
+            models.insert_watched(when, WhereVideo::Reference(video));
+        }
+
+        models
+    }
+
+    #[test]
+    fn test_group_channels_by_handle() {
+        let mut models = models_with_channels(&[
+            ("https://www.youtube.com/user/foo", "Foo"),
+            ("https://www.youtube.com/@Foo", "Foo"),
+            ("https://www.youtube.com/channel/UCfoo", "Foo"),
+            ("https://www.youtube.com/@bar", "Bar"),
+        ]);
+
+        assert_eq!(models.group_channels_by_handle(), 2);
+        let counts = models.top_channels(10);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].0, 3);
+        assert_eq!(counts[0].1.url, "https://www.youtube.com/channel/UCfoo");
+        assert!(models
+            .watches()
+            .iter()
+            .all(|w| models.channels.contains_key(&w.video.channel.url)));
+    }
+
+    #[test]
+    fn test_group_channels_by_handle_keeps_different_ids() {
+        let mut models = models_with_channels(&[
+            ("https://www.youtube.com/channel/UCmusic1", "Music"),
+            ("https://www.youtube.com/channel/UCmusic2", "Music"),
+            ("https://www.youtube.com/@music", "Music"),
+        ]);
+
+        // Neither ID is the only candidate for the handle
+        assert_eq!(models.group_channels_by_handle(), 0);
+        assert_eq!(models.top_channels(10).len(), 3);
+    }
+
+    #[test]
+    fn test_group_channels_by_handle_keeps_different_handles() {
+        let mut models = models_with_channels(&[
+            ("https://www.youtube.com/user/foo", "Gaming"),
+            ("https://www.youtube.com/@foo", "Gaming"),
+            ("https://www.youtube.com/@other", "Gaming"),
+            ("https://www.youtube.com/channel/UCgaming", "Gaming"),
+        ]);
+
+        assert_eq!(models.group_channels_by_handle(), 1);
+        let urls = models
+            .top_channels(10)
+            .into_iter()
+            .map(|(_, channel)| channel.url.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                "https://www.youtube.com/@foo",
+                "https://www.youtube.com/@other",
+                "https://www.youtube.com/channel/UCgaming"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_out_of_order_watches() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let video = models.insert_video(
+            "video".into(),
+            "Video".into(),
+            WhereChannel::Reference(channel),
+        );
+
+        // Newest first, with one watch that jumps back an hour
+        for when in [
+            "2021-11-07T03:00:00-05:00",
+            "2021-11-07T02:00:00-05:00",
+            "2021-11-07T02:30:00-05:00",
+            "2021-11-07T01:00:00-05:00",
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        assert_eq!(models.count_out_of_order_watches(), 1);
+    }
+
+    #[test]
+    fn test_count_watches_by_period() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let video = models.insert_video(
+            "video".into(),
+            "Video".into(),
+            WhereChannel::Reference(channel),
+        );
+
+        // 2021-01-01 is in ISO week 53 of 2020
+        for when in [
+            "2021-01-01T12:00:00-05:00",
+            "2021-01-04T12:00:00-05:00",
+            "2021-05-01T23:30:00-04:00",
+        ] {
+            models.insert_watched(date(when), WhereVideo::Reference(video.clone()));
+        }
+
+        let weeks = models.count_watches_by_period(Period::Week);
+        assert_eq!(
+            weeks.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("2020-W53".to_string(), 1),
+                ("2021-W01".to_string(), 1),
+                ("2021-W17".to_string(), 1)
+            ]
+        );
+
+        let quarters = models.count_watches_by_period(Period::Quarter);
+        assert_eq!(quarters["2021-Q1"], 2);
+        assert_eq!(quarters["2021-Q2"], 1);
+
+        let days = models.count_watches_by_period(Period::Day);
+        assert_eq!(days["2021-05-01"], 1);
+    }
+
+    #[test]
+    fn test_timezone_coverage() {
+        let mut models = Models::new();
+        assert_eq!(models.timezone_coverage(), 1.0);
+
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let video = models.insert_video(
+            "video".into(),
+            "Video".into(),
+            WhereChannel::Reference(channel),
+        );
+        let when = date("2023-01-01T10:00:00-06:00");
+        models.insert_watched(when, WhereVideo::Reference(video.clone()));
+        models.insert_watched(when, WhereVideo::Reference(video.clone()));
+        models.insert_watched(when, WhereVideo::Reference(video.clone()));
+        models.insert_watched_ambiguous_offset(when, WhereVideo::Reference(video));
+
+        assert_eq!(models.timezone_coverage(), 0.75);
+    }
+
+    #[test]
+    fn test_is_music() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let song = models.insert_video(
+            "https://music.youtube.com/watch?v=abcdefghijk".into(),
+            "Song".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+        let video = models.insert_video(
+            "https://www.youtube.com/watch?v=abcdefghijk".into(),
+            "Video".into(),
+            WhereChannel::Reference(channel),
+        );
+
+        assert!(song.is_music());
+        assert!(!video.is_music());
+    }
+
+    #[test]
+    fn test_remove_shorts() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        let short = models.insert_video(
+            "https://www.youtube.com/shorts/abcdefghijk".into(),
+            "Short".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+        let video = models.insert_video(
+            "https://www.youtube.com/watch?v=abcdefghijk".into(),
+            "Video".into(),
+            WhereChannel::Reference(channel),
+        );
+        let when = date("2023-01-01T10:00:00Z");
+        models.insert_watched(when, WhereVideo::Reference(short.clone()));
+        models.insert_watched(when, WhereVideo::Reference(short.clone()));
+        models.insert_watched(when, WhereVideo::Reference(video.clone()));
+
+        assert!(short.is_short());
+        assert!(!video.is_short());
+
+        let removed = models.remove_watches_matching(|watched| watched.video.is_short());
+
+        assert_eq!(removed, 2);
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+    }
+
+    #[test]
+    fn test_channel_lookups() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("channel".into(), "Channel".into());
+        models.insert_video(
+            "a".into(),
+            "A".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+        models.insert_video(
+            "b".into(),
+            "B".into(),
+            WhereChannel::Reference(channel.clone()),
+        );
+
+        let url = "a".to_string();
+        let found = models.channel_for_video(WhereVideo::Structure(VideoMatcher {
+            url: Some(&url),
+            title: None,
+            channel: None,
+        }));
+        assert_eq!(found, Some(channel.clone()));
+        assert_eq!(
+            models.count_videos_for_channel(WhereChannel::Reference(channel)),
+            2
+        );
+
+        let missing = "missing".to_string();
+        let found = models.channel_for_video(WhereVideo::Structure(VideoMatcher {
+            url: Some(&missing),
+            title: None,
+            channel: None,
+        }));
+        assert_eq!(found, None);
+        assert_eq!(
+            models.count_videos_for_channel(WhereChannel::Structure(ChannelMatcher {
+                url: Some(&missing),
+                name: None,
+                ignore_case: false,
+            })),
+            0
+        );
+    }
+
+    #[test]
+    fn test_count_videos_for_channel() {
+        let mut models = Models::new();
+        let a = models.insert_channel("a".into(), "A".into());
+        let b = models.insert_channel("b".into(), "B".into());
+        for (url, channel) in [("1", &a), ("2", &a), ("3", &b)] {
+            models.insert_video(
+                url.into(),
+                url.into(),
+                WhereChannel::Reference(channel.clone()),
+            );
+        }
+
+        assert_eq!(
+            models.count_videos_for_channel(WhereChannel::Reference(a)),
+            2
+        );
+        let name = "b".to_string();
+        assert_eq!(
+            models.count_videos_for_channel(WhereChannel::Structure(
+                ChannelMatcher::name_ignoring_case(&name)
+            )),
+            1
+        );
+        assert_eq!(models.count_videos_for_channel(WhereChannel::Any), 3);
+    }
+
+    fn models_with_conflict(policy: ChannelConflictPolicy) -> Models {
+        let mut models = Models::new();
+        models.set_channel_conflict_policy(policy);
+
+        // The second conflicting row is the same conflict
+        for channel_url in ["old-channel", "new-channel", "new-channel"] {
+            let channel = models.find_or_create_channel(&channel_url.into(), &"Channel".into());
+            let video = models.find_or_create_video("video".into(), "Video".into(), channel);
+            models.insert_watched(date("2023-01-01T10:00:00Z"), WhereVideo::Reference(video));
+        }
+        models.resolve_channel_conflicts();
+
+        models
+    }
+
+    #[test]
+    fn test_channel_conflict_first() {
+        let models = models_with_conflict(ChannelConflictPolicy::First);
+
+        assert_eq!(models.channel_conflicts(), 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+        let counts = models.count_watched_by_channel();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["old-channel"].0, 3);
+    }
+
+    #[test]
+    fn test_channel_conflict_last() {
+        let models = models_with_conflict(ChannelConflictPolicy::Last);
+
+        assert_eq!(models.channel_conflicts(), 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+        let counts = models.count_watched_by_channel();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["new-channel"].0, 3);
+        // The replaced channel has no videos left
+        assert_eq!(models.channels.len(), 1);
+        assert!(models.channels.contains_key("new-channel"));
+    }
+
+    #[test]
+    fn test_channel_conflict_last_keeps_used_channel() {
+        let mut models = Models::new();
+        models.set_channel_conflict_policy(ChannelConflictPolicy::Last);
+        let old = models.find_or_create_channel(&"old-channel".into(), &"Old".into());
+        let new = models.find_or_create_channel(&"new-channel".into(), &"New".into());
+        models.find_or_create_video("video".into(), "Video".into(), old.clone());
+        models.find_or_create_video("other".into(), "Other".into(), old);
+        models.find_or_create_video("video".into(), "Video".into(), new);
+        models.resolve_channel_conflicts();
+
+        assert_eq!(models.channels.len(), 2);
+        assert_eq!(models.videos["other"].channel.url, "old-channel");
+    }
+
+    #[test]
+    fn test_ids_from_urls() {
+        let channel = Rc::new(Channel {
+            url: "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng".into(),
+            name: "Benn Jordan".into(),
+        });
+        assert_eq!(channel.channel_id(), Some("UCshObcm-nLhbu8MY50EZ5Ng"));
+
+        let video = |url: &str| Video {
+            url: url.into(),
+            title: String::new(),
+            channel: channel.clone(),
+        };
+        assert_eq!(
+            video("https://www.youtube.com/watch?v=rtTWtzWav8I").video_id(),
+            Some("rtTWtzWav8I")
+        );
+        assert_eq!(
+            video("https://www.youtube.com/watch?v=rtTWtzWav8I&t=10s").video_id(),
+            Some("rtTWtzWav8I")
+        );
+        assert_eq!(
+            video("https://www.youtube.com/shorts/rtTWtzWav8I").video_id(),
+            Some("rtTWtzWav8I")
+        );
+        assert_eq!(video("https://www.youtube.com/").video_id(), None);
+        assert_eq!(
+            video("https://www.youtube.com/").id_or_url(),
+            "https://www.youtube.com/"
+        );
     }
 }
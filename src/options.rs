@@ -0,0 +1,932 @@
+use std::fmt;
+
+use crate::export::{Column, ExportOptions, TimeRounding};
+use crate::model::{ChannelConflictPolicy, MonthDay, Period, DEFAULT_WEEKEND};
+use crate::parser::{Locale, ParseOptions};
+use crate::report::{Page, ReportOptions};
+use crate::utf8_reader::Encoding;
+
+const DEFAULT_DATA_PATH: &str = "data/watch-history.html";
+const DEFAULT_TOP: usize = 50;
+/// Standard deviations above the daily mean for a binge day, unless
+/// `--outlier-k` says otherwise.
+const DEFAULT_OUTLIER_K: f64 = 3.0;
+/// The upper ends of the watches per channel histogram's bins, unless `--bins`
+/// says otherwise.
+const DEFAULT_BINS: [usize; 4] = [1, 5, 10, 50];
+
+/// Command line options. Flags may appear in any order; the single positional
+/// argument is the path to the history file.
+#[derive(Debug)]
+pub struct Options {
+    pub data_path: String,
+    /// With `compare a b`, the second history. `data_path` holds the first.
+    pub compare: Option<String>,
+    /// When set, repeat watches of the same video within this window are
+    /// counted once.
+    pub collapse_window: Option<chrono::Duration>,
+    /// Keep only IDs and counts while parsing, to save memory.
+    pub counts_only_memory: bool,
+    /// Only print the number of videos and watches.
+    pub count_only: bool,
+    /// Only print the report's summary stats.
+    pub summary_only: bool,
+    /// Only print the watches of this video URL.
+    pub watch_count_of: Option<String>,
+    /// Only print the watches on this day of each year.
+    pub on_day: Option<MonthDay>,
+    /// Only print videos sharing a title with another video.
+    pub find_dupes: bool,
+    /// Only print every channel with its watch count.
+    pub list_channels: bool,
+    /// Only print every video with its watch count, or write it to `csv`.
+    pub list_videos: bool,
+    /// Only print the first watch from this channel.
+    pub first_watch_of: Option<String>,
+    /// Only print the last watch from this channel.
+    pub last_watch_of: Option<String>,
+    /// Path to write a CSV export to.
+    pub csv: Option<String>,
+    /// Path to write each channel's first watch date to, as CSV.
+    pub channel_timeline: Option<String>,
+    /// Path to write the number of watches on each day to, as CSV.
+    pub timeline_csv: Option<String>,
+    /// Path to write a Parquet export to.
+    pub parquet: Option<String>,
+    /// Path to a JSON object of upload dates by video ID or URL.
+    pub upload_dates: Option<String>,
+    /// Look up upload dates and durations with the YouTube Data API.
+    pub enrich: bool,
+    /// YouTube Data API key for `enrich`, instead of the environment.
+    pub api_key: Option<String>,
+    /// Write video and channel IDs instead of URLs in CSV and JSON output.
+    pub ids_only: bool,
+    /// Round exported timestamps.
+    pub round_time: Option<TimeRounding>,
+    /// CSV columns, in order. Empty for the default set.
+    pub columns: Vec<Column>,
+    /// Leave out YouTube Music plays.
+    pub no_music: bool,
+    /// Leave out watches from channels with these names, ignoring case.
+    pub exclude_channels: Vec<String>,
+    /// Only include watches on or after this local date.
+    pub since: Option<chrono::NaiveDate>,
+    /// Only include watches on or before this local date.
+    pub until: Option<chrono::NaiveDate>,
+    /// Cache the history limited to `since` and `until` separately.
+    pub freeze_range: bool,
+    /// Keep a random sample of this many watches.
+    pub sample: Option<usize>,
+    /// Seeds `sample`, so the same watches are picked each run.
+    pub seed: Option<u64>,
+    /// Adds a section counting watches per period.
+    pub group_by: Option<Period>,
+    /// Adds a section with the hour of day profile of each weekday.
+    pub hour_profile: bool,
+    /// Adds a histogram of how many channels were watched how often.
+    pub channel_histogram: bool,
+    /// The upper ends of the histogram's bins, ascending.
+    pub bins: Vec<usize>,
+    /// Channels watched fewer times in a year are left out of that year.
+    pub min_year_watches: usize,
+    /// Standard deviations above the daily mean that make a binge day.
+    pub outlier_k: f64,
+    /// Use UTC instead of each watch's own offset for dates and hours.
+    pub utc: bool,
+    /// The days counted as the weekend in the weekday and weekend split.
+    pub weekend: Vec<chrono::Weekday>,
+    /// Number of entries in the top video and channel lists.
+    pub top: usize,
+    /// Adds a section listing this many videos for each top channel.
+    pub videos_per_channel: Option<usize>,
+    pub format: Format,
+    /// Which channel to keep for a video listed under several channels.
+    pub channel_conflicts: ChannelConflictPolicy,
+    /// The language of an HTML export, instead of detecting it.
+    pub locale: Option<Locale>,
+    /// How invalid UTF-8 in an HTML export is decoded.
+    pub encoding: Encoding,
+    /// Skip JSON rows that can't be read.
+    pub skip_errors: bool,
+    /// Keep watches of ads in JSON exports.
+    pub include_ads: bool,
+    /// Show the input around a parse error.
+    pub explain: bool,
+    pub shorts: ShortsFilter,
+    /// Print where time was spent.
+    pub profile: bool,
+    /// Print data quality checks.
+    pub validate: bool,
+    /// Only print the cache's path and metadata.
+    pub cache_info: bool,
+    /// Drop videos and channels without watches, rewriting the cache.
+    pub prune: bool,
+    /// Remove watches dated in the future.
+    pub drop_future: bool,
+    /// Order of watches in exports.
+    pub sort: ExportOrder,
+    /// Merge channels listed under different URL forms.
+    pub group_channels_by_handle: bool,
+    /// Only show this slice of the top video and channel lists.
+    pub page: Option<Page>,
+    /// Report the top lists for each of these date ranges instead.
+    pub periods: Vec<NamedPeriod>,
+}
+
+/// A named date range from `--period`, like `college=2016-01-01:2020-06-01`.
+/// Both dates are inclusive local dates, like `--since` and `--until`, and
+/// either can be left out for an open range.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NamedPeriod {
+    pub name: String,
+    pub since: Option<chrono::NaiveDate>,
+    pub until: Option<chrono::NaiveDate>,
+}
+
+impl std::str::FromStr for NamedPeriod {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, range) = s.split_once('=').ok_or(())?;
+        let (since, until) = range.split_once(':').ok_or(())?;
+        let date = |date: &str| match date {
+            "" => Ok(None),
+            date => date.parse().map(Some).map_err(|_| ()),
+        };
+
+        if name.is_empty() {
+            return Err(());
+        }
+
+        Ok(NamedPeriod {
+            name: name.to_string(),
+            since: date(since)?,
+            until: date(until)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportOrder {
+    /// Oldest first.
+    Chronological,
+    /// The order of the input file.
+    File,
+}
+
+impl std::str::FromStr for ExportOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chronological" => Ok(ExportOrder::Chronological),
+            "file" => Ok(ExportOrder::File),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether YouTube Shorts are included in the report.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ShortsFilter {
+    Include,
+    Exclude,
+    Only,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Console,
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "console" => Ok(Format::Console),
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            data_path: DEFAULT_DATA_PATH.into(),
+            compare: None,
+            collapse_window: None,
+            counts_only_memory: false,
+            count_only: false,
+            summary_only: false,
+            watch_count_of: None,
+            on_day: None,
+            find_dupes: false,
+            list_channels: false,
+            list_videos: false,
+            first_watch_of: None,
+            last_watch_of: None,
+            csv: None,
+            channel_timeline: None,
+            timeline_csv: None,
+            upload_dates: None,
+            enrich: false,
+            api_key: None,
+            parquet: None,
+            ids_only: false,
+            round_time: None,
+            columns: Vec::new(),
+            no_music: false,
+            exclude_channels: Vec::new(),
+            since: None,
+            until: None,
+            freeze_range: false,
+            sample: None,
+            seed: None,
+            group_by: None,
+            hour_profile: false,
+            channel_histogram: false,
+            bins: DEFAULT_BINS.to_vec(),
+            min_year_watches: 0,
+            weekend: DEFAULT_WEEKEND.to_vec(),
+            top: DEFAULT_TOP,
+            videos_per_channel: None,
+            format: Format::Console,
+            channel_conflicts: ChannelConflictPolicy::default(),
+            locale: None,
+            encoding: Encoding::default(),
+            skip_errors: false,
+            include_ads: false,
+            explain: false,
+            shorts: ShortsFilter::Include,
+            profile: false,
+            validate: false,
+            cache_info: false,
+            prune: false,
+            utc: false,
+            outlier_k: DEFAULT_OUTLIER_K,
+            periods: Vec::new(),
+            drop_future: false,
+            sort: ExportOrder::Chronological,
+            group_channels_by_handle: false,
+            page: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OptionsError {
+    TooManyArguments,
+    MissingArgument(String),
+    ConflictingFlags(String, String),
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+    },
+    /// The flag needs a Cargo feature this build doesn't have.
+    Unavailable {
+        flag: String,
+        feature: String,
+    },
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::TooManyArguments => write!(f, "Too many arguments"),
+            OptionsError::MissingArgument(argument) => write!(f, "Missing {}", argument),
+            OptionsError::ConflictingFlags(a, b) => write!(f, "{} can't be used with {}", a, b),
+            OptionsError::UnknownFlag(flag) => write!(f, "Unknown flag {}", flag),
+            OptionsError::MissingValue(flag) => write!(f, "Missing value for {}", flag),
+            OptionsError::InvalidValue { flag, value } => {
+                write!(f, "Invalid value for {}: {}", flag, value)
+            }
+            OptionsError::Unavailable { flag, feature } => write!(
+                f,
+                "{} is not available, rebuild with `--features {}`",
+                flag, feature
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, OptionsError> {
+        let mut options = Options::default();
+        let mut paths: Vec<String> = Vec::new();
+        let mut page_number: Option<usize> = None;
+        let mut page_size: Option<usize> = None;
+        let mut lossy = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--collapse-window" => {
+                    let minutes = parse_value::<i64, _>(&mut args, &arg)?;
+                    // Duration::minutes panics past its largest value
+                    if minutes <= 0 || minutes > chrono::Duration::max_value().num_minutes() {
+                        return Err(OptionsError::InvalidValue {
+                            flag: arg,
+                            value: minutes.to_string(),
+                        });
+                    }
+                    options.collapse_window = Some(chrono::Duration::minutes(minutes));
+                }
+                "--since" => {
+                    options.since = Some(parse_value(&mut args, &arg)?);
+                }
+                "--until" => {
+                    options.until = Some(parse_value(&mut args, &arg)?);
+                }
+                "--freeze-range" => {
+                    options.freeze_range = true;
+                }
+                "--sample" => {
+                    options.sample = Some(parse_value(&mut args, &arg)?);
+                }
+                "--seed" => {
+                    options.seed = Some(parse_value(&mut args, &arg)?);
+                }
+                "--group-by" => {
+                    options.group_by = Some(parse_value(&mut args, &arg)?);
+                }
+                "--utc" => {
+                    options.utc = true;
+                }
+                "--hour-profile" => {
+                    options.hour_profile = true;
+                }
+                "--watches-per-channel-histogram" => {
+                    options.channel_histogram = true;
+                }
+                "--bins" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let bins: Result<Vec<usize>, _> = value.split(',').map(str::parse).collect();
+                    match bins {
+                        Ok(bins)
+                            if bins.first().is_some_and(|first| *first > 0)
+                                && bins.windows(2).all(|pair| pair[0] < pair[1]) =>
+                        {
+                            options.bins = bins
+                        }
+                        _ => return Err(OptionsError::InvalidValue { flag: arg, value }),
+                    }
+                }
+                "--top" => {
+                    options.top = parse_value(&mut args, &arg)?;
+                }
+                "--videos-per-channel" => {
+                    options.videos_per_channel = Some(parse_positive(&mut args, &arg)?);
+                }
+                "--format" => {
+                    options.format = parse_value(&mut args, &arg)?;
+                }
+                "--channel-conflicts" => {
+                    options.channel_conflicts = parse_value(&mut args, &arg)?;
+                }
+                "--locale" => {
+                    options.locale = Some(parse_value(&mut args, &arg)?);
+                }
+                "--encoding" => {
+                    options.encoding = parse_value(&mut args, &arg)?;
+                }
+                "--lossy" => {
+                    lossy = true;
+                }
+                "--skip-errors" => {
+                    options.skip_errors = true;
+                }
+                "--include-ads" => {
+                    options.include_ads = true;
+                }
+                "--explain" => {
+                    options.explain = true;
+                }
+                "--no-music" => {
+                    options.no_music = true;
+                }
+                "--exclude-channel" => {
+                    options.exclude_channels.push(next_value(&mut args, &arg)?);
+                }
+                "--exclude-shorts" => {
+                    options.shorts = ShortsFilter::Exclude;
+                }
+                "--only-shorts" => {
+                    options.shorts = ShortsFilter::Only;
+                }
+                "--profile" => {
+                    options.profile = true;
+                }
+                "--validate" => {
+                    options.validate = true;
+                }
+                "--cache-info" => {
+                    options.cache_info = true;
+                }
+                "--prune" => {
+                    options.prune = true;
+                }
+                "--drop-future" => {
+                    options.drop_future = true;
+                }
+                "--sort" => {
+                    options.sort = parse_value(&mut args, &arg)?;
+                }
+                "--group-channels-by-handle" => {
+                    options.group_channels_by_handle = true;
+                }
+                "--period" => {
+                    options.periods.push(parse_value(&mut args, &arg)?);
+                }
+                "--page" => {
+                    page_number = Some(parse_positive(&mut args, &arg)?);
+                }
+                "--page-size" => {
+                    page_size = Some(parse_positive(&mut args, &arg)?);
+                }
+                "--ids-only" => {
+                    options.ids_only = true;
+                }
+                "--counts-only-memory" => {
+                    options.counts_only_memory = true;
+                }
+                "--count-only" => {
+                    options.count_only = true;
+                }
+                "--summary-only" => {
+                    options.summary_only = true;
+                }
+                "--watch-count-of" => {
+                    options.watch_count_of = Some(next_value(&mut args, &arg)?);
+                }
+                "--on" => {
+                    options.on_day = Some(parse_value(&mut args, &arg)?);
+                }
+                "--on-this-day" => {
+                    options.on_day = Some(MonthDay::of(chrono::Local::now().date_naive()));
+                }
+                "--find-dupes" => {
+                    options.find_dupes = true;
+                }
+                "--list-channels" => {
+                    options.list_channels = true;
+                }
+                "--list-videos" => {
+                    options.list_videos = true;
+                }
+                "--first-watch-of" => {
+                    options.first_watch_of = Some(next_value(&mut args, &arg)?);
+                }
+                "--last-watch-of" => {
+                    options.last_watch_of = Some(next_value(&mut args, &arg)?);
+                }
+                "--round-time" => {
+                    options.round_time = Some(parse_value(&mut args, &arg)?);
+                }
+                "--columns" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let columns: Result<_, ()> = value.split(',').map(str::parse).collect();
+                    options.columns =
+                        columns.map_err(|_| OptionsError::InvalidValue { flag: arg, value })?;
+                }
+                "--outlier-k" => {
+                    options.outlier_k = parse_value(&mut args, &arg)?;
+                }
+                "--min-year-watches" => {
+                    options.min_year_watches = parse_value(&mut args, &arg)?;
+                }
+                "--weekend" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let days: Result<_, _> = value.split(',').map(str::parse).collect();
+                    options.weekend =
+                        days.map_err(|_| OptionsError::InvalidValue { flag: arg, value })?;
+                }
+                "--csv" => {
+                    options.csv = Some(next_value(&mut args, &arg)?);
+                }
+                "--channel-timeline" => {
+                    options.channel_timeline = Some(next_value(&mut args, &arg)?);
+                }
+                "--timeline-csv" => {
+                    options.timeline_csv = Some(next_value(&mut args, &arg)?);
+                }
+                "--parquet" => {
+                    if !cfg!(feature = "parquet") {
+                        return Err(OptionsError::Unavailable {
+                            flag: arg,
+                            feature: "parquet".into(),
+                        });
+                    }
+                    options.parquet = Some(next_value(&mut args, &arg)?);
+                }
+                "--upload-dates" => {
+                    options.upload_dates = Some(next_value(&mut args, &arg)?);
+                }
+                "--enrich" => {
+                    if !cfg!(feature = "youtube-api") {
+                        return Err(OptionsError::Unavailable {
+                            flag: arg,
+                            feature: "youtube-api".into(),
+                        });
+                    }
+                    options.enrich = true;
+                }
+                "--api-key" => {
+                    options.api_key = Some(next_value(&mut args, &arg)?);
+                }
+                flag if flag.starts_with("--") => {
+                    return Err(OptionsError::UnknownFlag(flag.into()));
+                }
+                _ => paths.push(arg),
+            }
+        }
+
+        let mut paths = paths.into_iter();
+        match paths.next() {
+            Some(command) if command == "compare" => {
+                options.data_path = paths
+                    .next()
+                    .ok_or_else(|| OptionsError::MissingArgument("files to compare".into()))?;
+                options.compare = Some(paths.next().ok_or_else(|| {
+                    OptionsError::MissingArgument("second file to compare".into())
+                })?);
+            }
+            Some(data_path) => options.data_path = data_path,
+            None => {}
+        }
+        if paths.next().is_some() {
+            return Err(OptionsError::TooManyArguments);
+        }
+
+        if options.freeze_range {
+            if options.since.is_none() && options.until.is_none() {
+                return Err(OptionsError::MissingArgument(
+                    "--since or --until for --freeze-range".into(),
+                ));
+            }
+            if options.counts_only_memory {
+                return Err(OptionsError::ConflictingFlags(
+                    "--counts-only-memory".into(),
+                    "--freeze-range".into(),
+                ));
+            }
+        }
+
+        if options.seed.is_some() && options.sample.is_none() {
+            return Err(OptionsError::MissingArgument("--sample for --seed".into()));
+        }
+
+        if options.counts_only_memory {
+            // Exports need the titles and URLs that this mode drops
+            let export = [
+                ("--csv", &options.csv),
+                ("--parquet", &options.parquet),
+                ("--channel-timeline", &options.channel_timeline),
+            ]
+            .into_iter()
+            .find(|(_, path)| path.is_some());
+            if let Some((flag, _)) = export {
+                return Err(OptionsError::ConflictingFlags(
+                    "--counts-only-memory".into(),
+                    flag.into(),
+                ));
+            }
+        }
+
+        if lossy {
+            if options.encoding != Encoding::Utf8 {
+                return Err(OptionsError::ConflictingFlags(
+                    "--lossy".into(),
+                    "--encoding".into(),
+                ));
+            }
+            options.encoding = Encoding::Lossy;
+        }
+
+        if page_number.is_some() || page_size.is_some() {
+            options.page = Some(Page {
+                number: page_number.unwrap_or(1),
+                size: page_size.unwrap_or(options.top),
+            });
+        }
+
+        Ok(options)
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            channel_conflicts: self.channel_conflicts,
+            profile: self.profile,
+            compact: self.counts_only_memory,
+            locale: self.locale,
+            skip_errors: self.skip_errors,
+            encoding: self.encoding,
+            include_ads: self.include_ads,
+        }
+    }
+
+    pub fn report_options(&self) -> ReportOptions {
+        ReportOptions {
+            top: self.top,
+            page: self.page,
+            videos_per_channel: self.videos_per_channel,
+            group_by: self.group_by,
+            hour_profile: self.hour_profile,
+            channel_histogram: self.channel_histogram.then(|| self.bins.clone()),
+            weekend: self.weekend.clone(),
+            min_year_watches: self.min_year_watches,
+            outlier_k: self.outlier_k,
+            utc: self.utc,
+            // Read by main, since it can fail
+            upload_dates: None,
+            durations: None,
+        }
+    }
+
+    pub fn export_options(&self) -> ExportOptions {
+        ExportOptions {
+            ids_only: self.ids_only,
+            round_time: self.round_time,
+            columns: self.columns.clone(),
+        }
+    }
+}
+
+/// Takes the value following `flag`.
+fn next_value<I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<String, OptionsError> {
+    args.next()
+        .ok_or_else(|| OptionsError::MissingValue(flag.into()))
+}
+
+/// Takes the value following `flag` and parses it with `FromStr`.
+fn parse_value<T: std::str::FromStr, I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<T, OptionsError> {
+    let value = next_value(args, flag)?;
+    value.parse().map_err(|_| OptionsError::InvalidValue {
+        flag: flag.into(),
+        value,
+    })
+}
+
+/// Takes the value following `flag`, which must be a number above zero.
+fn parse_positive<I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<usize, OptionsError> {
+    let value = parse_value::<usize, _>(args, flag)?;
+    if value == 0 {
+        return Err(OptionsError::InvalidValue {
+            flag: flag.into(),
+            value: value.to_string(),
+        });
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        Options::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.data_path, DEFAULT_DATA_PATH);
+        assert_eq!(options.collapse_window, None);
+        assert_eq!(options.top, DEFAULT_TOP);
+        assert_eq!(options.format, Format::Console);
+    }
+
+    #[test]
+    fn test_path_and_flags() {
+        let options = parse(&["--collapse-window", "30", "history.json"]).unwrap();
+        assert_eq!(options.data_path, "history.json");
+        assert_eq!(options.collapse_window, Some(chrono::Duration::minutes(30)));
+
+        let options = parse(&["--group-by", "week"]).unwrap();
+        assert_eq!(options.group_by, Some(Period::Week));
+
+        let options = parse(&["--top", "5", "--format", "json"]).unwrap();
+        assert_eq!(options.top, 5);
+        assert_eq!(options.format, Format::Json);
+
+        let options = parse(&["--format", "yaml"]).unwrap();
+        assert_eq!(options.format, Format::Yaml);
+
+        let options = parse(&["--page", "2"]).unwrap();
+        assert_eq!(
+            options.page,
+            Some(Page {
+                number: 2,
+                size: DEFAULT_TOP
+            })
+        );
+
+        let options = parse(&["--page", "2", "--top", "10"]).unwrap();
+        assert_eq!(
+            options.page,
+            Some(Page {
+                number: 2,
+                size: 10
+            })
+        );
+
+        let options = parse(&["compare", "a.html", "--top", "5", "b.json"]).unwrap();
+        assert_eq!(options.data_path, "a.html");
+        assert_eq!(options.compare, Some("b.json".into()));
+
+        let options = parse(&["--columns", "watched_at,video_id,channel_name"]).unwrap();
+        assert_eq!(
+            options.columns,
+            vec![Column::Timestamp, Column::VideoId, Column::ChannelName]
+        );
+
+        let options = parse(&["--since", "2023-01-01", "--freeze-range"]).unwrap();
+        assert_eq!(options.since, chrono::NaiveDate::from_ymd_opt(2023, 1, 1));
+        assert!(options.freeze_range);
+
+        let options = parse(&[
+            "--period",
+            "college=2016-01-01:2020-06-01",
+            "--period",
+            "now=2020-06-01:",
+        ])
+        .unwrap();
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(
+            options.periods,
+            [
+                NamedPeriod {
+                    name: "college".into(),
+                    since: date(2016, 1, 1),
+                    until: date(2020, 6, 1),
+                },
+                NamedPeriod {
+                    name: "now".into(),
+                    since: date(2020, 6, 1),
+                    until: None,
+                },
+            ]
+        );
+
+        let options = parse(&["--exclude-channel", "A", "--exclude-channel", "B"]).unwrap();
+        assert_eq!(options.exclude_channels, ["A", "B"]);
+
+        let options = parse(&["--on", "06-04"]).unwrap();
+        assert_eq!(options.on_day, Some(MonthDay { month: 6, day: 4 }));
+
+        let options = parse(&["--weekend", "Fri,Sat"]).unwrap();
+        assert_eq!(
+            options.weekend,
+            [chrono::Weekday::Fri, chrono::Weekday::Sat]
+        );
+        assert_eq!(parse(&[]).unwrap().weekend, DEFAULT_WEEKEND);
+
+        let options = parse(&["--watches-per-channel-histogram", "--bins", "1,10,100"]).unwrap();
+        assert_eq!(
+            options.report_options().channel_histogram,
+            Some(vec![1, 10, 100])
+        );
+        assert_eq!(parse(&[]).unwrap().report_options().channel_histogram, None);
+
+        let options = parse(&["--encoding", "windows-1252"]).unwrap();
+        assert_eq!(
+            options.parse_options().encoding,
+            Encoding::Windows1252Fallback
+        );
+
+        let options = parse(&["--sample", "1000", "--seed", "42"]).unwrap();
+        assert_eq!((options.sample, options.seed), (Some(1000), Some(42)));
+
+        let options = parse(&["--lossy"]).unwrap();
+        assert_eq!(options.parse_options().encoding, Encoding::Lossy);
+
+        let options = parse(&["--locale", "de"]).unwrap();
+        assert_eq!(options.parse_options().locale, Some(Locale::German));
+
+        let options = parse(&["--page-size", "20"]).unwrap();
+        assert_eq!(
+            options.page,
+            Some(Page {
+                number: 1,
+                size: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse(&["a.html", "b.html"]).unwrap_err(),
+            OptionsError::TooManyArguments
+        );
+        assert_eq!(
+            parse(&["compare", "a.html"]).unwrap_err(),
+            OptionsError::MissingArgument("second file to compare".into())
+        );
+        assert_eq!(
+            parse(&["--counts-only-memory", "--csv", "out.csv"]).unwrap_err(),
+            OptionsError::ConflictingFlags("--counts-only-memory".into(), "--csv".into())
+        );
+        assert_eq!(
+            parse(&["--columns", "video_id,views"]).unwrap_err(),
+            OptionsError::InvalidValue {
+                flag: "--columns".into(),
+                value: "video_id,views".into()
+            }
+        );
+        assert_eq!(
+            parse(&["--weekend", "Sat,Funday"]).unwrap_err(),
+            OptionsError::InvalidValue {
+                flag: "--weekend".into(),
+                value: "Sat,Funday".into()
+            }
+        );
+        for bins in ["0,5", "5,1", "1,1", "1,x"] {
+            assert_eq!(
+                parse(&["--bins", bins]).unwrap_err(),
+                OptionsError::InvalidValue {
+                    flag: "--bins".into(),
+                    value: bins.into()
+                }
+            );
+        }
+        assert_eq!(
+            parse(&["--period", "2016-01-01:2020-06-01"]).unwrap_err(),
+            OptionsError::InvalidValue {
+                flag: "--period".into(),
+                value: "2016-01-01:2020-06-01".into()
+            }
+        );
+        assert_eq!(
+            parse(&["--lossy", "--encoding", "cp1252"]).unwrap_err(),
+            OptionsError::ConflictingFlags("--lossy".into(), "--encoding".into())
+        );
+        assert_eq!(
+            parse(&["--seed", "42"]).unwrap_err(),
+            OptionsError::MissingArgument("--sample for --seed".into())
+        );
+        assert_eq!(
+            parse(&["--freeze-range"]).unwrap_err(),
+            OptionsError::MissingArgument("--since or --until for --freeze-range".into())
+        );
+        assert_eq!(
+            parse(&["--collapse-window"]).unwrap_err(),
+            OptionsError::MissingValue("--collapse-window".into())
+        );
+        assert_eq!(
+            parse(&["--collapse-window", "soon"]).unwrap_err(),
+            OptionsError::InvalidValue {
+                flag: "--collapse-window".into(),
+                value: "soon".into()
+            }
+        );
+        assert_eq!(
+            parse(&["--collapse-window", "9223372036854775807"]).unwrap_err(),
+            OptionsError::InvalidValue {
+                flag: "--collapse-window".into(),
+                value: "9223372036854775807".into()
+            }
+        );
+        if !cfg!(feature = "youtube-api") {
+            assert_eq!(
+                parse(&["--enrich"]).unwrap_err(),
+                OptionsError::Unavailable {
+                    flag: "--enrich".into(),
+                    feature: "youtube-api".into()
+                }
+            );
+        }
+        if !cfg!(feature = "parquet") {
+            assert_eq!(
+                parse(&["--parquet", "out.parquet"]).unwrap_err(),
+                OptionsError::Unavailable {
+                    flag: "--parquet".into(),
+                    feature: "parquet".into()
+                }
+            );
+        }
+        assert_eq!(
+            parse(&["--nope"]).unwrap_err(),
+            OptionsError::UnknownFlag("--nope".into())
+        );
+    }
+}
@@ -1,39 +1,52 @@
+use std::collections::VecDeque;
 use std::io::Read;
 use std::iter::Enumerate;
 use std::iter::Peekable;
+use std::sync::Arc;
+use std::time::Instant;
 
 use chrono::TimeZone;
 
 use crate::model::{Models, WhereVideo};
+use crate::parser::locale::Locale;
+use crate::parser::timezone::{self, Confidence};
+use crate::parser::{is_channel_url, ParseOptions, ParseStats};
 use crate::utf8_reader;
 use crate::utf8_reader::Utf8Iter;
 
-type Iter<R> = Peekable<Enumerate<Utf8Iter<R>>>;
+type Iter<I> = Peekable<Enumerate<I>>;
 
-// U+00A0 is a non-breaking space
-const ANCHOR_OPENING_TO_HREF: &str = "Watched\u{00A0}<a href=\"";
+/// What `ModelsParser` reads, a `Utf8Iter` or a borrowed one.
+trait Chars: Iterator<Item = Result<char, utf8_reader::Error>> {}
 
-// Examples:
-// Jun 29, 2021, 4:49:36 PM EDT
-// Aug 9, 2019, 4:26:40 PM EDT
-//
-// U+202F is a narrow non-breaking space
-const DATE_FORMAT: &str = "%h %e, %Y, %I:%M:%S\u{202F}%p %Z";
+impl<I: Iterator<Item = Result<char, utf8_reader::Error>>> Chars for I {}
 
 pub struct ModelsParser {
     models: Models,
+    locale: Locale,
     line: usize,
     column: usize,
     chars_read: usize,
+    stats: ParseStats,
 }
 
+/// The spellings of `<br>` seen in exports.
+const LINE_BREAKS: [&str; 3] = ["<br />", "<br/>", "<br>"];
+
+/// The date of a `DataRow` before its date has been read. `insert_row`
+/// rejects rows still holding it.
+const NO_DATE: chrono::DateTime<chrono::Utc> = chrono::DateTime::<chrono::Utc>::MIN_UTC;
+
+/// One watch, as read from the file.
 #[derive(Debug, Clone)]
-struct DataRow {
-    url: String,
-    title: String,
-    channel_name: String,
-    channel_url: String,
-    date: chrono::DateTime<chrono::FixedOffset>,
+pub struct DataRow {
+    pub url: String,
+    pub title: String,
+    pub channel_name: String,
+    pub channel_url: String,
+    pub date: chrono::DateTime<chrono::FixedOffset>,
+    /// The timezone abbreviation didn't identify a single offset.
+    pub offset_ambiguous: bool,
 }
 
 impl Default for DataRow {
@@ -43,56 +56,117 @@ impl Default for DataRow {
             title: String::new(),
             channel_name: String::new(),
             channel_url: String::new(),
-            date: chrono::DateTime::<chrono::FixedOffset>::MIN_UTC.into(),
+            date: NO_DATE.into(),
+            offset_ambiguous: false,
+        }
+    }
+}
+
+/// Iterator returned by `ModelsParser::rows`.
+#[cfg(test)]
+pub struct Rows<'a, R: Read> {
+    parser: &'a mut ModelsParser,
+    chars: Iter<Utf8Iter<R>>,
+    done: bool,
+}
+
+#[cfg(test)]
+impl<R: Read> Iterator for Rows<'_, R> {
+    type Item = Result<DataRow, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
+
+        let row = self.parser.next_data_row_timed(&mut self.chars).transpose();
+        if !matches!(row, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        row
     }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct Location {
+    /// Characters read, after any byte order mark. This is also the index of
+    /// the character at this location.
     pub chars: usize,
+    /// The column, starting at 1. Columns count characters, not bytes, so an
+    /// emoji is one column.
     pub columns: usize,
+    /// The line, starting at 1. A `\n` starts the next line at column 1.
     pub lines: usize,
 }
 
 impl ModelsParser {
-    pub fn new() -> Self {
+    pub fn new(options: &ParseOptions) -> Self {
+        let mut models = Models::new();
+        models.set_channel_conflict_policy(options.channel_conflicts);
+        models.set_compact(options.compact);
+
         Self {
-            models: Models::new(),
+            models,
+            locale: options.locale.unwrap_or_default(),
             line: 0,
             column: 0,
             chars_read: 0,
+            stats: ParseStats::default(),
         }
     }
 
+    // Called by the tests and the fuzz targets
+    #[allow(dead_code)]
     pub fn parse<R: Read>(&mut self, raw_chars: Utf8Iter<R>) -> Result<(), ParseError> {
-        let mut chars = raw_chars.enumerate().peekable();
+        self.parse_with_progress(raw_chars, &mut |_| {})
+    }
 
-        // Ensure at least one row can be read
-        match self.next_data_row(&mut chars)? {
-            Some(row) => {
-                self.insert_row(row)?;
-            }
-            None => {
-                return Err(ParseError::NoRows);
-            }
-        };
+    /// Like `parse`, calling `progress` with the number of rows parsed so far
+    /// after each row.
+    pub fn parse_with_progress<R: Read>(
+        &mut self,
+        mut raw_chars: Utf8Iter<R>,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<(), ParseError> {
+        let result = self.parse_rows(&mut (&mut raw_chars).enumerate().peekable(), progress);
+        self.stats.bytes = Some(raw_chars.bytes_read());
+        self.stats.chars = Some(self.chars_read);
+
+        result
+    }
 
-        loop {
-            match self.next_data_row(&mut chars)? {
-                Some(row) => {
-                    self.insert_row(row)?;
-                }
-                None => {
-                    // No more rows
-                    return Ok(());
-                }
-            }
+    fn parse_rows<I: Chars>(
+        &mut self,
+        chars: &mut Iter<I>,
+        progress: &mut dyn FnMut(usize),
+    ) -> Result<(), ParseError> {
+        // A file without any rows is an empty history, like a JSON `[]`
+        while let Some(row) = self.next_data_row_timed(chars)? {
+            self.insert_row_timed(row)?;
+            self.stats.rows += 1;
+            progress(self.stats.rows);
+        }
+
+        Ok(())
+    }
+
+    /// Reads rows one at a time instead of building `Models`, for callers that
+    /// want to aggregate as they go. The parser's location is still tracked
+    /// for errors, but nothing is inserted into its models. Iteration stops
+    /// after the first error.
+    #[cfg(test)]
+    pub fn rows<R: Read>(&mut self, raw_chars: Utf8Iter<R>) -> Rows<'_, R> {
+        Rows {
+            parser: self,
+            chars: raw_chars.enumerate().peekable(),
+            done: false,
         }
     }
 
-    pub fn to_models(self) -> Models {
-        self.models
+    pub fn into_models(mut self) -> (Models, ParseStats) {
+        self.models.resolve_channel_conflicts();
+        (self.models, self.stats)
     }
 
     /// The current line of the parser, starting at 1.
@@ -113,7 +187,34 @@ impl ModelsParser {
         }
     }
 
+    fn next_data_row_timed<I: Chars>(
+        &mut self,
+        chars: &mut Iter<I>,
+    ) -> Result<Option<DataRow>, ParseError> {
+        let start = Instant::now();
+        let row = self.next_data_row(chars);
+        self.stats.tokenize += start.elapsed();
+
+        row
+    }
+
+    fn insert_row_timed(&mut self, row: DataRow) -> Result<(), ParseError> {
+        let start = Instant::now();
+        let result = self.insert_row(row);
+        self.stats.build += start.elapsed();
+
+        result
+    }
+
     fn insert_row(&mut self, row: DataRow) -> Result<(), ParseError> {
+        if row.date == NO_DATE {
+            // A row should never get here without a date, but if one does it
+            // would skew every time based report
+            return Err(ParseError::MissingDate {
+                location: self.location(),
+            });
+        }
+
         let channel = self
             .models
             .find_or_create_channel(&row.channel_url, &row.channel_name);
@@ -121,19 +222,24 @@ impl ModelsParser {
             .models
             .find_or_create_video(row.url, row.title, channel);
 
-        self.models
-            .insert_watched(row.date, WhereVideo::Reference(video));
+        if row.offset_ambiguous {
+            self.models
+                .insert_watched_ambiguous_offset(row.date, WhereVideo::Reference(video));
+        } else {
+            self.models
+                .insert_watched(row.date, WhereVideo::Reference(video));
+        }
 
         Ok(())
     }
 
-    fn next_data_row<R: Read>(
+    fn next_data_row<I: Chars>(
         &mut self,
-        chars: &mut Iter<R>,
+        chars: &mut Iter<I>,
     ) -> Result<Option<DataRow>, ParseError> {
         let mut row = DataRow::default();
 
-        let skip_result = self.skip_to(chars, ANCHOR_OPENING_TO_HREF.into());
+        let skip_result = self.skip_to(chars, self.locale.anchor());
         match skip_result {
             Ok(()) => {}
             Err(ParseError::UnterminatedInput { .. }) => {
@@ -146,118 +252,165 @@ impl ModelsParser {
 
         row.url = self.read_until(chars, "\"")?;
         self.skip_to(chars, ">")?;
-        row.title = self.read_until(chars, "<")?;
+        row.title = decode_entities(&self.read_title(chars)?);
         // Skip just before the channel link
-        self.skip_to(chars, "<br />")?;
-
-        match self.peek(chars)? {
-            '<' => {
-                // Parse channel
-                self.skip_to(chars, "\"")?;
-                row.channel_url = self.read_until(chars, "\"")?;
-                self.skip_to(chars, ">")?;
-                row.channel_name = self.read_until(chars, "<")?;
-                self.skip_to(chars, "<br />")?;
+        self.skip_line_break(chars)?;
+
+        // Lines with links come before the date. The channel is usually the
+        // first one, but extra links like hashtags can come before it.
+        let mut first_link = None;
+        loop {
+            match self.peek(chars)? {
+                '<' => {
+                    let line = self.read_until_any(chars, &LINE_BREAKS)?.0;
+                    let links = links(&line);
+                    if let Some((url, name)) = links.iter().find(|(url, _)| is_channel_url(url)) {
+                        row.channel_url = url.to_string();
+                        row.channel_name = decode_entities(name);
+                        break;
+                    }
+                    first_link = first_link.or(links
+                        .first()
+                        .map(|(url, name)| (url.to_string(), name.to_string())));
+                }
+                c if self.locale.anchor().starts_with(c) => {
+                    // Sometimes, the channel is missing and instead it has the
+                    // text "Watched at <time>". We skip this text to the start
+                    // of the timestamp.
+                    self.skip_line_break(chars)?;
+                    break;
+                }
+                _ => break,
             }
-            'W' => {
-                // Sometimes, the channel is missing and instead it has the text
-                // "Watched at <time>". We skip this text to the start of the
-                // timestamp.
-                self.skip_to(chars, "<br />")?;
+        }
+        if row.channel_url.is_empty() {
+            // No link looked like a channel, fall back to the first one
+            if let Some((url, name)) = first_link {
+                row.channel_url = url;
+                row.channel_name = decode_entities(&name);
             }
-            _ => (),
         }
 
+        let date_location = self.location();
         let date_string = self.read_until(chars, "\n")?;
-        row.date = chrono::Utc
-            .datetime_from_str(date_string.as_str(), DATE_FORMAT)
-            .map_err(|error| ParseError::DateParseError {
-                location: self.location(),
-                invalid_date: date_string,
-                error,
-            })?
-            .into();
+        (row.date, row.offset_ambiguous) = self.parse_date(date_string, date_location)?;
 
         Ok(Some(row))
     }
 
-    fn skip_to<R: Read>(&mut self, chars: &mut Iter<R>, s: &str) -> Result<(), ParseError> {
-        let mut closest = String::with_capacity(s.len());
-        let mut closest_location = Location::default();
-
-        let mut found = String::with_capacity(s.len());
-        let mut found_location = Location::default();
-
-        for (_, maybe_char) in chars {
-            let char = maybe_char.map_err(|e| ParseError::from_utf8_error(&e, self.location()))?;
-            self.chars_read += 1;
+    /// Parses a date like "Jun 29, 2021, 4:49:36 PM EDT", in the format of the
+    /// parser's locale. Also returns true if
+    /// the timezone abbreviation was ambiguous or unknown.
+    fn parse_date(
+        &self,
+        date_string: String,
+        location: Location,
+    ) -> Result<(chrono::DateTime<chrono::FixedOffset>, bool), ParseError> {
+        let trimmed = date_string.trim();
+        let (local, abbreviation) = trimmed
+            .rsplit_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+
+        let local = chrono::NaiveDateTime::parse_from_str(
+            &self.locale.english_month(local),
+            self.locale.date_format(),
+        )
+        .map_err(|error| ParseError::DateParseError {
+            location,
+            invalid_date: date_string.clone(),
+            error,
+        })?;
+
+        let (offset, confidence) = timezone::offset_for_abbreviation(abbreviation);
+        let date = offset.from_local_datetime(&local).unwrap();
+
+        Ok((date, confidence != Confidence::Known))
+    }
 
-            if char == '\n' {
-                self.line += 1;
-                self.column = 0;
-            } else {
-                self.column += 1;
-            }
+    fn skip_to<I: Chars>(&mut self, chars: &mut Iter<I>, s: &str) -> Result<(), ParseError> {
+        self.skip_to_any(chars, &[s]).map(|_| ())
+    }
 
-            let want = match s.chars().nth(found.chars().count()) {
-                Some(c) => c,
-                None => {
-                    panic!(
-                        "skip_to internal desync: `found` is longer than s (s: {}, found: {})",
-                        s, found
-                    )
-                }
-            };
-
-            if want == char {
-                if found.len() == 0 {
-                    found_location = Location {
-                        lines: self.line,
-                        columns: self.column,
-                        chars: self.chars_read,
-                    };
-                }
+    /// Like `skip_to`, stopping after whichever of `terminators` appears
+    /// first. Returns the index of the one that matched.
+    fn skip_to_any<I: Chars>(
+        &mut self,
+        chars: &mut Iter<I>,
+        terminators: &[&str],
+    ) -> Result<usize, ParseError> {
+        self.scan(chars, terminators, |_| {})
+    }
 
-                found.push(char);
+    /// Skips past a line break, which exports spell `<br />`, `<br/>` or
+    /// `<br>`.
+    fn skip_line_break<I: Chars>(&mut self, chars: &mut Iter<I>) -> Result<(), ParseError> {
+        self.skip_to_any(chars, &LINE_BREAKS).map(|_| ())
+    }
 
-                if &found == s {
-                    return Ok(());
-                }
-            } else {
-                if found.len() > 0 {
-                    found.push(char);
-                    if found.chars().count() > closest.chars().count() {
-                        closest = found.clone();
-                        closest_location = found_location.clone();
-                    }
-                }
+    /// Reads a video's title up to its closing tag. Titles can contain "<",
+    /// and links of their own, like hashtags, whose text is kept.
+    fn read_title<I: Chars>(&mut self, chars: &mut Iter<I>) -> Result<String, ParseError> {
+        let mut title = String::new();
 
-                found.clear();
+        loop {
+            let (read, matched) = self.read_until_any(chars, &["</a>", "<a "])?;
+            title.push_str(&read);
+            if matched == 0 {
+                return Ok(title);
             }
+
+            self.skip_to(chars, ">")?;
+            title.push_str(&self.read_until(chars, "</a>")?);
         }
+    }
 
-        Err(ParseError::UnterminatedInput {
-            expected: s.into(),
-            closest: if closest.is_empty() {
-                None
-            } else {
-                Some((closest, closest_location))
-            },
-        })
-        .into()
+    fn read_until<I: Chars>(&mut self, chars: &mut Iter<I>, s: &str) -> Result<String, ParseError> {
+        self.read_until_any(chars, &[s]).map(|(read, _)| read)
     }
 
-    fn read_until<R: Read>(&mut self, chars: &mut Iter<R>, s: &str) -> Result<String, ParseError> {
-        let mut closest = String::with_capacity(s.len());
-        let mut closest_location = Location::default();
+    /// Like `read_until`, stopping after whichever of `terminators` appears
+    /// first. Returns what was read before it and the index of the one that
+    /// matched.
+    fn read_until_any<I: Chars>(
+        &mut self,
+        chars: &mut Iter<I>,
+        terminators: &[&str],
+    ) -> Result<(String, usize), ParseError> {
+        let mut read = String::new();
+        let mut buf = [0; 4];
+        let matched = self.scan(chars, terminators, |char| {
+            push_collapse_whitespace(&mut read, char.encode_utf8(&mut buf))
+        })?;
 
-        let mut found = String::with_capacity(s.len());
-        let mut found_location = Location::default();
+        Ok((read, matched))
+    }
 
-        let mut read = String::new();
+    /// Reads characters until one of `terminators` has been read, passing the
+    /// characters before it to `keep`. Returns the index of the terminator
+    /// that matched. When two end on the same character, the earlier one in
+    /// `terminators` wins.
+    fn scan<I: Chars>(
+        &mut self,
+        chars: &mut Iter<I>,
+        terminators: &[&str],
+        mut keep: impl FnMut(char),
+    ) -> Result<usize, ParseError> {
+        let terminators = terminators
+            .iter()
+            .map(|terminator| terminator.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // How much of each terminator has been seen, and where that started
+        let mut found = vec![(0, Location::default()); terminators.len()];
+        // Characters that might be part of a terminator, not passed to `keep`
+        // yet
+        let mut pending = VecDeque::new();
+
+        let mut closest: Option<(String, Location)> = None;
 
         for (_, maybe_char) in chars {
             let char = maybe_char.map_err(|e| ParseError::from_utf8_error(&e, self.location()))?;
+            let at = self.location();
             self.chars_read += 1;
 
             if char == '\n' {
@@ -267,64 +420,57 @@ impl ModelsParser {
                 self.column += 1;
             }
 
-            let want = match s.chars().nth(found.chars().count()) {
-                Some(c) => c,
-                None => {
-                    panic!(
-                        "read_until internal desync: `found` is longer than s (s: {}, found: {})",
-                        s, found
-                    )
-                }
-            };
+            pending.push_back(char);
 
-            if want == char {
-                // Found part of string, don't add this to read
-
-                if found.len() == 0 {
-                    found_location = Location {
-                        lines: self.line,
-                        columns: self.column,
-                        chars: self.chars_read,
-                    };
-                }
+            for (i, terminator) in terminators.iter().enumerate() {
+                let (len, location) = &mut found[i];
 
-                found.push(char);
+                if *len > 0 && terminator[*len] != char {
+                    // Remember the longest partial match, with the character
+                    // that broke it, for the error message
+                    let partial = terminator[..*len].iter().chain([&char]).collect::<String>();
+                    let longer = closest.as_ref().is_none_or(|(closest, _)| {
+                        partial.chars().count() > closest.chars().count()
+                    });
+                    if longer {
+                        closest = Some((partial, location.clone()));
+                    }
 
-                if &found == s {
-                    return Ok(read);
+                    *len = 0;
                 }
-            } else {
-                if found.len() > 0 {
-                    found.push(char);
 
-                    if found.chars().count() > closest.chars().count() {
-                        closest = found.clone();
-                        closest_location = found_location.clone();
+                if terminator[*len] == char {
+                    if *len == 0 {
+                        *location = at.clone();
                     }
 
-                    // We found part of the string, but then it didn't match.
-                    // Append what we saw to read.
-                    push_collapse_whitespace(&mut read, &found);
+                    *len += 1;
 
-                    found.clear();
-                } else {
-                    push_collapse_whitespace(&mut read, &String::from(char));
+                    if *len == terminator.len() {
+                        let before = pending.len() - terminator.len();
+                        pending.drain(..before).for_each(&mut keep);
+                        return Ok(i);
+                    }
                 }
             }
+
+            let longest = found.iter().map(|(len, _)| *len).max().unwrap_or(0);
+            while pending.len() > longest {
+                keep(pending.pop_front().unwrap());
+            }
         }
 
         Err(ParseError::UnterminatedInput {
-            expected: s.into(),
-            closest: if closest.is_empty() {
-                None
-            } else {
-                Some((closest, closest_location))
-            },
+            expected: terminators
+                .iter()
+                .map(|terminator| terminator.iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join(" or "),
+            closest,
         })
-        .into()
     }
 
-    fn peek<R: Read>(&mut self, chars: &mut Iter<R>) -> Result<char, ParseError> {
+    fn peek<I: Chars>(&mut self, chars: &mut Iter<I>) -> Result<char, ParseError> {
         match chars.peek() {
             Some((_, Ok(char))) => Ok(*char),
             Some((_, Err(e))) => Err(ParseError::from_utf8_error(e, self.location())),
@@ -336,6 +482,29 @@ impl ModelsParser {
     }
 }
 
+/// The URL and text of each `<a href="...">text</a>` in `html`.
+fn links(html: &str) -> Vec<(&str, &str)> {
+    let mut links = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<a href=\"") {
+        rest = &rest[start + "<a href=\"".len()..];
+        let Some((url, after)) = rest.split_once('"') else {
+            break;
+        };
+        let Some((_, after)) = after.split_once('>') else {
+            break;
+        };
+        let Some((text, after)) = after.split_once("</a>") else {
+            break;
+        };
+        links.push((url, text));
+        rest = after;
+    }
+
+    links
+}
+
 /// Appends s to target, converting whitespace characters to U+0020 SPACE.
 /// Consecutive whitespace is collapsed.
 fn push_collapse_whitespace(target: &mut String, s: &str) {
@@ -363,6 +532,7 @@ fn push_collapse_whitespace(target: &mut String, s: &str) {
 //
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum ParseError {
     UnterminatedInput {
         expected: String,
@@ -374,17 +544,34 @@ pub enum ParseError {
     },
     IoError {
         location: Location,
-        error: String,
+        /// Shared so the error stays `Clone`.
+        error: Arc<std::io::Error>,
     },
     DateParseError {
         location: Location,
         invalid_date: String,
         error: chrono::ParseError,
     },
-    NoRows,
+    /// A row was about to be inserted without its date.
+    MissingDate {
+        location: Location,
+    },
 }
 
 impl ParseError {
+    /// Where in the input the error happened, if it's known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ParseError::UnterminatedInput { closest, .. } => {
+                closest.as_ref().map(|(_, location)| location)
+            }
+            ParseError::InvalidUtf8 { location, .. }
+            | ParseError::IoError { location, .. }
+            | ParseError::DateParseError { location, .. }
+            | ParseError::MissingDate { location } => Some(location),
+        }
+    }
+
     fn from_utf8_error(error: &utf8_reader::Error, location: Location) -> ParseError {
         match error {
             utf8_reader::Error::InvalidBytes(bytes) => ParseError::InvalidUtf8 {
@@ -393,7 +580,7 @@ impl ParseError {
             },
             utf8_reader::Error::IoError(error) => ParseError::IoError {
                 location,
-                error: error.to_string(),
+                error: Arc::new(std::io::Error::new(error.kind(), error.to_string())),
             },
             utf8_reader::Error::End => ParseError::UnterminatedInput {
                 expected: "1 more character".into(),
@@ -405,8 +592,353 @@ impl ParseError {
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:#?}", self)
+        match self {
+            ParseError::UnterminatedInput { expected, closest } => {
+                write!(f, "file ended while looking for {:?}", expected)?;
+                if let Some((closest, location)) = closest {
+                    write!(
+                        f,
+                        ", closest was {:?} at line {} column {}",
+                        closest, location.lines, location.columns
+                    )?;
+                }
+                Ok(())
+            }
+            ParseError::InvalidUtf8 { location, bytes } => write!(
+                f,
+                "invalid UTF-8 {:02X?} at line {} column {}",
+                bytes, location.lines, location.columns
+            ),
+            ParseError::IoError { location, .. } => write!(
+                f,
+                "couldn't read the file at line {} column {}",
+                location.lines, location.columns
+            ),
+            ParseError::DateParseError {
+                location,
+                invalid_date,
+                ..
+            } => write!(
+                f,
+                "couldn't parse the date {:?} at line {} column {}",
+                invalid_date.trim(),
+                location.lines,
+                location.columns
+            ),
+            ParseError::MissingDate { location } => write!(
+                f,
+                "the row ending at line {} column {} has no date",
+                location.lines, location.columns
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::IoError { error, .. } => Some(error.as_ref()),
+            ParseError::DateParseError { error, .. } => Some(error),
+            _ => None,
+        }
     }
 }
 
-impl std::error::Error for ParseError {}
+/// Decodes the HTML entities Takeout uses in titles and names.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    // `&amp;` goes last so "&amp;lt;" decodes to "&lt;", not "<"
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::WhereChannel;
+
+    #[test]
+    fn test_rows() {
+        let input = "<div class=\"content-cell\">Watched\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">An Addictive Alternative To DAWs</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />Jun 4, 2023, 12:07:59\u{202F}AM EDT\n</div>\n";
+        let input = input.repeat(2);
+
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let rows = parser
+            .rows(Utf8Iter::new(input.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].title, "An Addictive Alternative To DAWs");
+        assert_eq!(rows[0].channel_name, "Benn Jordan");
+        assert_eq!(rows[0].date.to_rfc3339(), "2023-06-04T00:07:59-04:00");
+
+        let (models, _) = parser.into_models();
+        assert_eq!(models.watches().len(), 0);
+    }
+
+    #[test]
+    fn test_title_with_angle_brackets() {
+        let input = "Watched\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">I <3 synths &lt;3 &amp;lt;</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Rock &amp; Roll</a><br />Jun 4, 2023, 12:07:59\u{202F}AM EDT\n";
+
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let rows = parser
+            .rows(Utf8Iter::new(input.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(rows[0].title, "I <3 synths <3 &lt;");
+        assert_eq!(rows[0].channel_name, "Rock & Roll");
+    }
+
+    #[test]
+    fn test_read_until_any() {
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let mut chars = Utf8Iter::new("a <<b>c d<br/>e".as_bytes())
+            .enumerate()
+            .peekable();
+
+        // A partial match doesn't hide one starting inside it
+        assert_eq!(
+            parser
+                .read_until_any(&mut chars, &["<br />", "<b>"])
+                .unwrap(),
+            ("a <".to_string(), 1)
+        );
+        assert_eq!(
+            parser
+                .read_until_any(&mut chars, &["<br />", "<br/>"])
+                .unwrap(),
+            ("c d".to_string(), 1)
+        );
+        match parser.skip_to_any(&mut chars, &["<br>", "</a>"]) {
+            Err(ParseError::UnterminatedInput { expected, .. }) => {
+                assert_eq!(expected, "<br> or </a>")
+            }
+            result => panic!("expected UnterminatedInput, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_line_break_spellings() {
+        let parse = |input: &str| {
+            let mut parser = ModelsParser::new(&ParseOptions::default());
+            parser.parse(Utf8Iter::new(input.as_bytes())).unwrap();
+            parser.into_models().0
+        };
+
+        let expected = parse(include_str!("../../tests/fixtures/watch-history.html"));
+        assert_eq!(
+            parse(include_str!("../../tests/fixtures/watch-history-br.html")),
+            expected
+        );
+        assert_eq!(
+            parse(include_str!(
+                "../../tests/fixtures/watch-history-br-slash.html"
+            )),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_extra_links() {
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let input = include_str!("../../tests/fixtures/watch-history-links.html");
+        parser.parse(Utf8Iter::new(input.as_bytes())).unwrap();
+        let (models, _) = parser.into_models();
+
+        let watches = models
+            .watches()
+            .iter()
+            .map(|watched| {
+                let video = &watched.video;
+                (video.title.as_str(), video.channel.name.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            watches,
+            [
+                ("An Addictive Alternative To DAWs #music", "Benn Jordan"),
+                ("Never Gonna Give You Up", "Rick Astley"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_date() {
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let row = DataRow {
+            url: "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            ..DataRow::default()
+        };
+
+        assert!(matches!(
+            parser.insert_row(row),
+            Err(ParseError::MissingDate { .. })
+        ));
+        assert!(parser.into_models().0.watches().is_empty());
+    }
+
+    #[test]
+    fn test_golden_file() {
+        let input = include_str!("../../tests/fixtures/watch-history.html");
+
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        parser.parse(Utf8Iter::new(input.as_bytes())).unwrap();
+        let (models, stats) = parser.into_models();
+
+        // Every watch got its date
+        assert!(models
+            .watches()
+            .iter()
+            .all(|watched| watched.when != NO_DATE));
+
+        // The whole file is consumed
+        assert_eq!(stats.bytes, Some(input.len()));
+        assert_eq!(stats.chars, Some(input.chars().count()));
+        assert_eq!(stats.rows, models.watches().len());
+
+        let date = |s| chrono::DateTime::parse_from_rfc3339(s).unwrap();
+        let mut expected = Models::new();
+        let benn = expected.insert_channel(
+            "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng".into(),
+            "Benn Jordan".into(),
+        );
+        let rick = expected.insert_channel(
+            "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw".into(),
+            "Rick Astley".into(),
+        );
+        // Rows without a channel get an empty one
+        let missing = expected.insert_channel(String::new(), String::new());
+        let daws = expected.insert_video(
+            "https://www.youtube.com/watch?v=rtTWtzWav8I".into(),
+            "An Addictive Alternative To DAWs".into(),
+            WhereChannel::Reference(benn),
+        );
+        let rickroll = expected.insert_video(
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ".into(),
+            "Rock & Roll: \"Never\" <Gonna> Give You Up '87".into(),
+            WhereChannel::Reference(rick),
+        );
+        let old = expected.insert_video(
+            "https://www.youtube.com/watch?v=Xb8Uo4Tq1AI".into(),
+            "Old Upload".into(),
+            WhereChannel::Reference(missing),
+        );
+        expected.insert_watched(
+            date("2023-06-04T00:07:59-04:00"),
+            WhereVideo::Reference(daws.clone()),
+        );
+        expected.insert_watched(
+            date("2022-12-01T21:30:00-05:00"),
+            WhereVideo::Reference(rickroll),
+        );
+        expected.insert_watched(
+            date("2022-01-02T16:49:36-05:00"),
+            WhereVideo::Reference(old),
+        );
+        expected.insert_watched(
+            date("2019-08-09T16:26:40-04:00"),
+            WhereVideo::Reference(daws),
+        );
+
+        assert_eq!(models, expected);
+        // Equality compares instants, check the offsets were kept too
+        let offsets = models
+            .watches()
+            .iter()
+            .map(|watched| watched.when.offset().local_minus_utc() / 3600)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [-4, -5, -5, -4]);
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error;
+
+        let input = "Watched\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">Title</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />Someday\n";
+
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let error = parser.parse(Utf8Iter::new(input.as_bytes())).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "couldn't parse the date \"Someday\" at line 1 column 166"
+        );
+        assert!(error.source().unwrap().is::<chrono::ParseError>());
+        assert!(ParseError::MissingDate {
+            location: Location::default()
+        }
+        .source()
+        .is_none());
+    }
+
+    #[test]
+    fn test_location_columns() {
+        // Columns count characters, so the emoji and accent are one column
+        // each, and the second line starts again at column 1
+        let row = "Watched\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">🎵 Café</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />";
+        let input = format!("<html>\n{row}Someday\n");
+
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let error = parser.parse(Utf8Iter::new(input.as_bytes())).unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(location.lines, 2);
+        assert_eq!(location.columns, row.chars().count() + 1);
+        assert_eq!(
+            location.chars,
+            input
+                .find("Someday")
+                .map(|i| input[..i].chars().count())
+                .unwrap()
+        );
+
+        // A partial match points at its first character
+        let input = "<html>\nWatched\u{00A0}<a href=\"https://www.youtube.com/watch?v=x\">🎵 T</b";
+        let mut parser = ModelsParser::new(&ParseOptions::default());
+        let error = parser.parse(Utf8Iter::new(input.as_bytes())).unwrap_err();
+        let location = error.location().unwrap();
+        let line = input.lines().nth(1).unwrap();
+        assert_eq!(location.lines, 2);
+        assert_eq!(
+            location.columns,
+            line.find("</b")
+                .map(|i| line[..i].chars().count() + 1)
+                .unwrap()
+        );
+        assert!(error.to_string().ends_with(&format!(
+            "closest was \"</b\" at line 2 column {}",
+            location.columns
+        )));
+    }
+
+    #[test]
+    fn test_locales() {
+        let row = |anchor: &str, date: &str| {
+            format!("{anchor}https://www.youtube.com/watch?v=rtTWtzWav8I\">Title</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />{date}\n")
+        };
+
+        for (locale, date) in [
+            (Locale::Spanish, "4 jun 2023, 6:07:59 CEST"),
+            (Locale::French, "4 juin 2023, 06:07:59 CEST"),
+        ] {
+            let input = row(locale.anchor(), date);
+            let mut parser = ModelsParser::new(&ParseOptions {
+                locale: Some(locale),
+                ..ParseOptions::default()
+            });
+            let rows = parser
+                .rows(Utf8Iter::new(input.as_bytes()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+
+            assert_eq!(rows[0].date.to_rfc3339(), "2023-06-04T06:07:59+02:00");
+        }
+    }
+}
@@ -1,10 +1,18 @@
+use std::time::{Duration, Instant};
 use std::{error::Error, io::Read};
 
+use chrono::{DateTime, FixedOffset};
+use serde::de::{DeserializeSeed, Deserializer, Error as _, SeqAccess, Visitor};
 use serde::Deserialize;
 
 use crate::model::{Models, WhereVideo};
+use crate::parser::{is_channel_url, ParseOptions, ParseStats};
 
 const DEFAULT_CHANNEL: &str = "(hidden)";
+const YOUTUBE_PRODUCT: &str = "YouTube";
+const YOUTUBE_HEADERS: [&str; 2] = ["YouTube", "YouTube Music"];
+/// The `details` entry of a watch that was an ad.
+const AD_DETAIL: &str = "From Google Ads";
 
 #[derive(Deserialize, Debug, PartialEq)]
 struct DataRow {
@@ -14,10 +22,15 @@ struct DataRow {
     title_url: String,
     #[serde(default)]
     subtitles: Vec<Subtitles>,
-    time: String,
+    /// Checked per row in `parse`, so a missing time can name the row.
+    #[serde(default)]
+    time: Option<String>,
+    #[serde(default)]
     products: Vec<String>,
-    #[serde(rename = "activityControls")]
+    #[serde(rename = "activityControls", default)]
     activity_controls: Vec<String>,
+    #[serde(default)]
+    details: Vec<Details>,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -27,57 +40,218 @@ struct Subtitles {
     url: String,
 }
 
-pub fn parse<R: Read>(reader: R) -> Result<Models, Box<dyn Error>> {
-    let rows = parse_data_rows(reader)?;
+#[derive(Deserialize, Debug, PartialEq)]
+struct Details {
+    name: String,
+}
+
+/// Parses a JSON export, calling `progress` with the number of rows handled so
+/// far after each row. Rows are inserted as they're read, so the whole array
+/// is never held in memory.
+pub fn parse<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+    progress: &mut dyn FnMut(usize),
+) -> Result<(Models, ParseStats), Box<dyn Error>> {
+    let start = Instant::now();
     let mut models = Models::new();
+    models.set_channel_conflict_policy(options.channel_conflicts);
+    models.set_compact(options.compact);
+
+    let mut builder = Builder {
+        models,
+        options,
+        progress,
+        total: 0,
+        skipped: Vec::new(),
+        ads: 0,
+        build: Duration::ZERO,
+        failed: None,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = (&mut builder)
+        .deserialize(&mut deserializer)
+        .and_then(|()| deserializer.end());
+    if let Some(error) = builder.failed {
+        return Err(error.into());
+    }
+    result?;
+
+    let mut models = builder.models;
+    let build_start = Instant::now();
+    models.resolve_channel_conflicts();
+    let build = builder.build + build_start.elapsed();
 
-    for row in rows {
-        if row.title == "Visited YouTube Music" {
-            // Skip rows that are not videos
-            continue;
+    let stats = ParseStats {
+        // Reading and inserting are interleaved, tokenizing is the rest
+        tokenize: start.elapsed().saturating_sub(build),
+        build,
+        rows: builder.total - builder.skipped.len(),
+        skipped: builder.skipped,
+        ads: builder.ads,
+        ..ParseStats::default()
+    };
+
+    Ok((models, stats))
+}
+
+/// Inserts the rows of the top level array while it's deserialized. Each
+/// element is read as a `serde_json::Value` first, so one that doesn't fit
+/// `DataRow` fails only that row. The file still has to be valid JSON.
+struct Builder<'a> {
+    models: Models,
+    options: &'a ParseOptions,
+    progress: &'a mut dyn FnMut(usize),
+    /// Rows read so far, including skipped ones and ads.
+    total: usize,
+    skipped: Vec<String>,
+    ads: usize,
+    /// Time spent inserting rows.
+    build: Duration,
+    /// Why a row failed when errors aren't skipped. Deserializing stops there.
+    failed: Option<String>,
+}
+
+impl Builder<'_> {
+    fn row(&mut self, value: serde_json::Value) -> Result<(), String> {
+        let row = serde_json::from_value::<DataRow>(value).map_err(|error| error.to_string())?;
+        if !self.options.include_ads && is_ad(&row) {
+            self.ads += 1;
+            return Ok(());
         }
 
-        // TODO maybe model functions should take a &str to avoid allocating here
-        let default_channel = DEFAULT_CHANNEL.to_owned();
+        let start = Instant::now();
+        let result = insert_row(&mut self.models, row);
+        self.build += start.elapsed();
+        result
+    }
+}
 
-        let channel_subtitle = row.subtitles.get(0);
-        let channel_name = match channel_subtitle {
-            Some(s) => &s.name,
-            None => &default_channel,
-        };
-        let channel_url = match channel_subtitle {
-            Some(s) => &s.url,
-            None => &default_channel,
-        };
-        let date = chrono::DateTime::parse_from_rfc3339(row.time.as_str())?;
+impl<'de> DeserializeSeed<'de> for &mut Builder<'_> {
+    type Value = ();
 
-        let title = if row.title.starts_with("Watched ") {
-            &row.title[8..]
-        } else {
-            &row.title
-        };
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
 
-        let channel = models.find_or_create_channel(&channel_url, &channel_name);
-        let video = models.find_or_create_video(row.title_url, title.into(), channel);
+impl<'de> Visitor<'de> for &mut Builder<'_> {
+    type Value = ();
 
-        models.insert_watched(date, WhereVideo::Reference(video));
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "an array of activity rows")
     }
 
-    Ok(models)
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            self.total += 1;
+
+            if let Err(error) = self.row(value) {
+                let error = format!("Row {}: {}", self.total, error);
+                if !self.options.skip_errors {
+                    self.failed = Some(error);
+                    return Err(A::Error::custom("stopped at a row that couldn't be read"));
+                }
+                self.skipped.push(error);
+            }
+
+            (self.progress)(self.total);
+        }
+
+        Ok(())
+    }
 }
 
-fn parse_data_rows<R: Read>(reader: R) -> Result<Vec<DataRow>, serde_json::Error> {
-    serde_json::from_reader(reader).map_err(|e| e.into())
+/// Adds the watch in `row`, unless it's for another product or isn't a video.
+fn insert_row(models: &mut Models, row: DataRow) -> Result<(), String> {
+    if !is_youtube_row(&row) {
+        // Combined "My Activity" exports include other products like
+        // Search, skip them
+        return Ok(());
+    }
+
+    if row.title == "Visited YouTube Music" {
+        // Skip rows that are not videos
+        return Ok(());
+    }
+
+    // TODO maybe model functions should take a &str to avoid allocating here
+    let default_channel = DEFAULT_CHANNEL.to_owned();
+
+    let channel_subtitle = channel_subtitle(&row);
+    let channel_name = match channel_subtitle {
+        Some(s) => &s.name,
+        None => &default_channel,
+    };
+    let channel_url = match channel_subtitle {
+        Some(s) => &s.url,
+        None => &default_channel,
+    };
+    let Some(time) = &row.time else {
+        return Err(format!("\"{}\" has no time", row.title));
+    };
+    let date = parse_time(time)?;
+
+    let title = if row.title.starts_with("Watched ") {
+        &row.title[8..]
+    } else {
+        &row.title
+    };
+
+    let channel = models.find_or_create_channel(channel_url, channel_name);
+    let video = models.find_or_create_video(row.title_url, title.into(), channel);
+
+    models.insert_watched(date, WhereVideo::Reference(video));
+
+    Ok(())
+}
+
+/// Parses an RFC 3339 time like `2023-06-04T04:07:59.107Z`. Takeout varies the
+/// fractional seconds (none, milliseconds, microseconds) and writes the offset
+/// as `Z`, `+00:00` or occasionally `+0000`.
+fn parse_time(time: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(time)
+        .or_else(|_| DateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .map_err(|error| format!("Invalid time \"{}\": {}", time, error))
+}
+
+/// The channel is the first subtitle. Later subtitles hold extra context, and
+/// rows for deleted or private videos sometimes have a first subtitle that
+/// isn't a channel, those use `DEFAULT_CHANNEL`.
+fn channel_subtitle(row: &DataRow) -> Option<&Subtitles> {
+    row.subtitles
+        .first()
+        .filter(|subtitle| !subtitle.name.is_empty() && is_channel_url(&subtitle.url))
+}
+
+/// Rows without `products` are judged by their header alone.
+fn is_youtube_row(row: &DataRow) -> bool {
+    YOUTUBE_HEADERS.contains(&row.header.as_str())
+        && (row.products.is_empty()
+            || row
+                .products
+                .iter()
+                .any(|product| product == YOUTUBE_PRODUCT))
+}
+
+/// Ads shown before or during a video are listed as watches of the ad.
+fn is_ad(row: &DataRow) -> bool {
+    row.details.iter().any(|detail| detail.name == AD_DETAIL)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::WhereWatched;
+
+    fn strict_rows(input: &str) -> Vec<DataRow> {
+        serde_json::from_str(input).unwrap()
+    }
 
     #[test]
     fn test_empty() {
         let input = "[]";
-        let actual = parse_data_rows(input.as_bytes()).unwrap();
+        let actual = strict_rows(input);
         let expected: Vec<DataRow> = vec![];
 
         assert_eq!(expected, actual);
@@ -100,7 +274,7 @@ mod tests {
             }]
         "#;
 
-        let actual = parse_data_rows(input.as_bytes()).unwrap();
+        let actual = strict_rows(input);
         let expected: Vec<DataRow> = vec![DataRow {
             header: "YouTube".into(),
             title: "Watched An Addictive Alternative To DAWs".into(),
@@ -109,11 +283,212 @@ mod tests {
                 name: "Benn Jordan".into(),
                 url: "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng".into(),
             }],
-            time: "2023-06-04T04:07:59.107Z".into(),
+            time: Some("2023-06-04T04:07:59.107Z".into()),
             products: vec!["YouTube".into()],
             activity_controls: vec!["YouTube watch history".into()],
+            details: vec![],
         }];
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_mixed_products() {
+        let input = r#"
+            [{
+                "header": "Search",
+                "title": "Searched for rust borrow checker",
+                "titleUrl": "https://www.google.com/search?q\u003drust+borrow+checker",
+                "time": "2023-06-04T04:10:00.000Z",
+                "products": ["Search"],
+                "activityControls": ["Web \u0026 App Activity"]
+            }, {
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "subtitles": [{
+                    "name": "Benn Jordan",
+                    "url": "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng"
+                }],
+                "time": "2023-06-04T04:07:59.107Z",
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"]
+            }, {
+                "header": "Maps",
+                "title": "Viewed area in Maps",
+                "time": "2023-06-04T04:00:00.000Z",
+                "products": ["Maps"],
+                "activityControls": ["Web \u0026 App Activity"]
+            }]
+        "#;
+
+        let (models, _) = parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).unwrap();
+
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+    }
+
+    #[test]
+    fn test_missing_products() {
+        let input = r#"
+            [{
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "time": "2023-06-04T04:07:59.107Z"
+            }]
+        "#;
+
+        let (models, _) = parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).unwrap();
+
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+    }
+
+    #[test]
+    fn test_ads() {
+        let input = r#"
+            [{
+                "header": "YouTube",
+                "title": "Watched Some Ad",
+                "titleUrl": "https://www.youtube.com/watch?v\u003dxxxxxxxxxxx",
+                "time": "2023-06-04T04:07:00.000Z",
+                "products": ["YouTube"],
+                "details": [{
+                    "name": "From Google Ads"
+                }],
+                "activityControls": ["YouTube watch history"]
+            }, {
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "time": "2023-06-04T04:07:59.107Z",
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"]
+            }]
+        "#;
+
+        let (models, stats) =
+            parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).unwrap();
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(
+            models.watches()[0].video.title,
+            "An Addictive Alternative To DAWs"
+        );
+        assert_eq!(stats.ads, 1);
+
+        let options = ParseOptions {
+            include_ads: true,
+            ..ParseOptions::default()
+        };
+        let (models, stats) = parse(input.as_bytes(), &options, &mut |_| {}).unwrap();
+        assert_eq!(models.count_watches(WhereWatched::Any), 2);
+        assert_eq!(stats.ads, 0);
+    }
+
+    #[test]
+    fn test_missing_time() {
+        let input = r#"
+            [{
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "products": ["YouTube"]
+            }]
+        "#;
+
+        let error = parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Row 1: \"Watched An Addictive Alternative To DAWs\" has no time"
+        );
+    }
+
+    #[test]
+    fn test_skip_errors() {
+        let input = r#"
+            [{
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "time": "2023-06-04T04:07:59.107Z"
+            }, {
+                "header": "YouTube",
+                "title": ["not", "a", "string"],
+                "time": "2023-06-04T04:00:00.000Z"
+            }, {
+                "header": "YouTube",
+                "title": "Watched a video without a time"
+            }]
+        "#;
+
+        assert!(parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).is_err());
+
+        let options = ParseOptions {
+            skip_errors: true,
+            ..ParseOptions::default()
+        };
+        let (models, stats) = parse(input.as_bytes(), &options, &mut |_| {}).unwrap();
+
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(stats.skipped.len(), 2);
+        assert!(stats.skipped[0].starts_with("Row 2: "));
+    }
+
+    #[test]
+    fn test_subtitles() {
+        let input = r#"
+            [{
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "subtitles": [{
+                    "name": "Benn Jordan",
+                    "url": "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng"
+                }, {
+                    "name": "From your playlist"
+                }],
+                "time": "2023-06-04T04:07:59.107Z",
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"]
+            }, {
+                "header": "YouTube",
+                "title": "Watched a video that has been removed",
+                "titleUrl": "https://www.youtube.com/watch?v\u003dxxxxxxxxxxx",
+                "subtitles": [{
+                    "name": "Some context",
+                    "url": "https://www.youtube.com/playlist?list\u003dPL123"
+                }],
+                "time": "2023-06-04T04:00:00.000Z",
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"]
+            }]
+        "#;
+
+        let (models, _) = parse(input.as_bytes(), &ParseOptions::default(), &mut |_| {}).unwrap();
+
+        let channels = models
+            .top_channels(10)
+            .into_iter()
+            .map(|(_, channel)| channel.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(channels, vec![DEFAULT_CHANNEL, "Benn Jordan"]);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let expected = DateTime::parse_from_rfc3339("2023-06-04T04:07:59Z").unwrap();
+        assert_eq!(parse_time("2023-06-04T04:07:59Z").unwrap(), expected);
+        assert_eq!(parse_time("2023-06-04T04:07:59+00:00").unwrap(), expected);
+        assert_eq!(parse_time("2023-06-04T04:07:59+0000").unwrap(), expected);
+        assert_eq!(
+            parse_time("2023-06-04T04:07:59.107123Z")
+                .unwrap()
+                .timestamp_micros(),
+            expected.timestamp_micros() + 107123
+        );
+
+        let error = parse_time("June 4th").unwrap_err();
+        assert!(error.contains("\"June 4th\""));
+    }
 }
@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// The language of an HTML export. Takeout translates the text around each
+/// video link and formats dates for the account's language.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+    Spanish,
+    French,
+}
+
+// Month names as Takeout writes them, with the abbreviation chrono's `%b` reads
+const SPANISH_MONTHS: &[(&str, &str)] = &[
+    ("ene", "Jan"),
+    ("feb", "Feb"),
+    ("mar", "Mar"),
+    ("abr", "Apr"),
+    ("may", "May"),
+    ("jun", "Jun"),
+    ("jul", "Jul"),
+    ("ago", "Aug"),
+    ("sept", "Sep"),
+    ("sep", "Sep"),
+    ("oct", "Oct"),
+    ("nov", "Nov"),
+    ("dic", "Dec"),
+];
+
+const FRENCH_MONTHS: &[(&str, &str)] = &[
+    ("janv.", "Jan"),
+    ("févr.", "Feb"),
+    ("mars", "Mar"),
+    ("avr.", "Apr"),
+    ("mai", "May"),
+    ("juin", "Jun"),
+    ("juil.", "Jul"),
+    ("août", "Aug"),
+    ("sept.", "Sep"),
+    ("oct.", "Oct"),
+    ("nov.", "Nov"),
+    ("déc.", "Dec"),
+];
+
+impl Locale {
+    const ALL: &'static [Locale] = &[
+        Locale::English,
+        Locale::German,
+        Locale::Spanish,
+        Locale::French,
+    ];
+
+    /// The text just before each video's URL.
+    ///
+    /// U+00A0 is a non-breaking space
+    pub fn anchor(self) -> &'static str {
+        match self {
+            Locale::English => "Watched\u{00A0}<a href=\"",
+            Locale::German => "Angesehen\u{00A0}<a href=\"",
+            Locale::Spanish => "Has visto\u{00A0}<a href=\"",
+            Locale::French => "Vous avez regardé\u{00A0}<a href=\"",
+        }
+    }
+
+    /// The format of a watch's local time, without the timezone.
+    ///
+    /// Examples:
+    /// Jun 29, 2021, 4:49:36 PM EDT
+    /// 29.06.2021, 16:49:36 MESZ
+    /// 29 jun 2021, 16:49:36 CEST
+    ///
+    /// U+202F is a narrow non-breaking space. Spelled out months are read with
+    /// `%b` after `english_month`.
+    pub fn date_format(self) -> &'static str {
+        match self {
+            Locale::English => "%h %e, %Y, %I:%M:%S\u{202F}%p",
+            Locale::German => "%d.%m.%Y, %H:%M:%S",
+            Locale::Spanish | Locale::French => "%e %b %Y, %H:%M:%S",
+        }
+    }
+
+    /// Replaces a month name in `date` with the English abbreviation, so
+    /// chrono can parse it.
+    pub fn english_month(self, date: &str) -> Cow<'_, str> {
+        let months = match self {
+            Locale::English | Locale::German => return Cow::Borrowed(date),
+            Locale::Spanish => SPANISH_MONTHS,
+            Locale::French => FRENCH_MONTHS,
+        };
+
+        let words = date.split(' ').map(|word| {
+            months
+                .iter()
+                .find(|(name, _)| *name == word)
+                .map_or(word, |(_, english)| english)
+        });
+        Cow::Owned(words.collect::<Vec<_>>().join(" "))
+    }
+
+    /// Finds the locale whose anchor appears in `start`, the beginning of an
+    /// export.
+    pub fn detect(start: &str) -> Option<Locale> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|locale| start.contains(locale.anchor()))
+    }
+}
+
+impl FromStr for Locale {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" | "english" => Ok(Locale::English),
+            "de" | "german" => Ok(Locale::German),
+            "es" | "spanish" => Ok(Locale::Spanish),
+            "fr" | "french" => Ok(Locale::French),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Locale::detect("<div>Angesehen\u{00A0}<a href=\"https://www.youtube.com/"),
+            Some(Locale::German)
+        );
+        assert_eq!(
+            Locale::detect("<div>Watched\u{00A0}<a href=\"https://www.youtube.com/"),
+            Some(Locale::English)
+        );
+        assert_eq!(Locale::detect("<html><body>"), None);
+    }
+
+    #[test]
+    fn test_english_month() {
+        assert_eq!(
+            Locale::French.english_month("4 août 2023, 00:07:59"),
+            "4 Aug 2023, 00:07:59"
+        );
+        assert_eq!(
+            Locale::Spanish.english_month("4 sept 2023, 0:07:59"),
+            "4 Sep 2023, 0:07:59"
+        );
+    }
+}
@@ -1,34 +1,275 @@
 mod html_parser;
 mod json_parser;
+mod locale;
+mod takeout;
+mod timezone;
 
 use std::error::Error;
-use std::io::BufReader;
-use std::io::Read;
+use std::io::{BufRead, BufReader, Read};
+use std::time::{Duration, Instant};
 
-use crate::model::Models;
-use crate::utf8_reader::Utf8Iter;
+use crate::model::{ChannelConflictPolicy, Models};
+use crate::utf8_reader::{Encoding, Utf8Iter};
 
 pub use html_parser::ParseError;
+pub use locale::Locale;
+// Streaming API, not used by the binary itself
+#[allow(unused_imports)]
+pub use html_parser::{DataRow, ModelsParser};
+
+#[cfg(test)]
+#[allow(unused_imports)]
+pub use html_parser::Rows;
 
 pub enum ParserType {
     Html,
     Json,
+    /// A Takeout archive, holding one of the above.
+    Zip,
+}
+
+/// Options shared by both parsers.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    pub channel_conflicts: ChannelConflictPolicy,
+    /// Read the whole file before parsing, so reading can be timed separately.
+    pub profile: bool,
+    /// Keep IDs instead of URLs, titles and names, see `Models::set_compact`.
+    pub compact: bool,
+    /// The language of an HTML export, detected from its start when `None`.
+    pub locale: Option<Locale>,
+    /// Skip JSON rows that can't be read instead of failing, see
+    /// `ParseStats::skipped`.
+    pub skip_errors: bool,
+    /// How an HTML export's invalid UTF-8 is handled. JSON must be UTF-8.
+    pub encoding: Encoding,
+    /// Keep JSON rows marked as ads, which are left out by default. HTML
+    /// exports don't mark them.
+    pub include_ads: bool,
 }
 
-pub fn parse_file(file_path: &str, parser_type: ParserType) -> Result<Models, Box<dyn Error>> {
+/// Where parsing spent its time. `read` is only measured when profiling,
+/// otherwise reading happens while tokenizing.
+#[derive(Debug, Default, Clone)]
+pub struct ParseStats {
+    pub read: Duration,
+    /// Decoding and splitting the input into rows.
+    pub tokenize: Duration,
+    /// Inserting rows into `Models`.
+    pub build: Duration,
+    /// The archive entry that was parsed, for `ParserType::Zip`.
+    pub zip_entry: Option<String>,
+    /// Why each row left out by `ParseOptions::skip_errors` was skipped.
+    pub skipped: Vec<String>,
+    /// Rows read into `Models`, not counting skipped ones.
+    pub rows: usize,
+    /// Bytes decoded, after any byte order mark. Only counted for HTML, JSON
+    /// is checked for truncation by its closing bracket.
+    pub bytes: Option<usize>,
+    /// Characters decoded, after any byte order mark. Only counted for HTML.
+    pub chars: Option<usize>,
+    /// JSON rows left out because they were ads, see
+    /// `ParseOptions::include_ads`.
+    pub ads: usize,
+}
+
+/// Parses the file at `file_path`, calling `progress` with the number of rows
+/// parsed so far as it goes.
+pub fn parse_file(
+    file_path: &str,
+    parser_type: ParserType,
+    options: &ParseOptions,
+    progress: impl FnMut(usize),
+) -> Result<(Models, ParseStats), Box<dyn Error>> {
     let file = std::fs::File::open(file_path)?;
-    parser(parser_type, BufReader::new(file))
+
+    if let ParserType::Zip = parser_type {
+        let mut archive = zip::ZipArchive::new(file)?;
+        let (name, parser_type) = takeout::find_history_entry(&archive)?;
+        let entry = archive.by_name(&name)?;
+
+        let (models, stats) = parse_reader(parser_type, entry, options, progress)?;
+        return Ok((
+            models,
+            ParseStats {
+                zip_entry: Some(name),
+                ..stats
+            },
+        ));
+    }
+
+    parse_reader(parser_type, file, options, progress)
+}
+
+/// Parses `data`, reading all of it up front when profiling so reading can be
+/// timed separately.
+fn parse_reader<R: Read>(
+    parser_type: ParserType,
+    mut data: R,
+    options: &ParseOptions,
+    progress: impl FnMut(usize),
+) -> Result<(Models, ParseStats), Box<dyn Error>> {
+    if options.profile {
+        let start = Instant::now();
+        let mut bytes = Vec::new();
+        data.read_to_end(&mut bytes)?;
+        let read = start.elapsed();
+
+        let (models, stats) =
+            parser_with_progress(parser_type, bytes.as_slice(), options, progress)?;
+        Ok((models, ParseStats { read, ..stats }))
+    } else {
+        parser_with_progress(
+            parser_type,
+            BufReader::with_capacity(DETECT_BYTES, data),
+            options,
+            progress,
+        )
+    }
+}
+
+/// Whether `url` is in one of the forms of a channel URL, for telling the
+/// channel apart from other links in a row.
+fn is_channel_url(url: &str) -> bool {
+    ["/channel/", "/@", "/user/", "/c/"]
+        .iter()
+        .any(|path| url.contains(path))
 }
 
-pub fn parser<F: Read>(parser_type: ParserType, data: F) -> Result<Models, Box<dyn Error>> {
+/// How much of an HTML export is buffered up front, enough to get past the
+/// styles in the header to the first rows for `Locale::detect`.
+const DETECT_BYTES: usize = 64 * 1024;
+
+/// Some Takeout files start with a byte order mark.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[cfg(test)]
+pub fn parser<F: BufRead>(
+    parser_type: ParserType,
+    data: F,
+    options: &ParseOptions,
+) -> Result<(Models, ParseStats), Box<dyn Error>> {
+    parser_with_progress(parser_type, data, options, |_| {})
+}
+
+/// Like `parser`, calling `progress` with the number of rows parsed so far
+/// after each row, for callers showing their own progress.
+pub fn parser_with_progress<F: BufRead>(
+    parser_type: ParserType,
+    mut data: F,
+    options: &ParseOptions,
+    mut progress: impl FnMut(usize),
+) -> Result<(Models, ParseStats), Box<dyn Error>> {
+    skip_bom(&mut data)?;
+
     match parser_type {
         ParserType::Html => {
-            let mut parser = html_parser::ModelsParser::new();
-            match parser.parse(Utf8Iter::new(data)) {
-                Ok(()) => Ok(parser.to_models()),
+            let mut options = options.clone();
+            if options.locale.is_none() {
+                let start = String::from_utf8_lossy(data.fill_buf()?);
+                options.locale = Locale::detect(&start);
+            }
+
+            let mut parser = html_parser::ModelsParser::new(&options);
+            match parser.parse_with_progress(
+                Utf8Iter::with_encoding(data, options.encoding),
+                &mut progress,
+            ) {
+                Ok(()) => Ok(parser.into_models()),
                 Err(error) => Err(error.into()),
             }
         }
-        ParserType::Json => json_parser::parse(data).map_err(|e| e.into()),
+        ParserType::Json => json_parser::parse(data, options, &mut progress),
+        ParserType::Zip => Err("ZIP archives can only be parsed from a file".into()),
+    }
+}
+
+/// Consumes a leading UTF-8 byte order mark, if there is one.
+fn skip_bom<F: BufRead>(data: &mut F) -> std::io::Result<()> {
+    if data.fill_buf()?.starts_with(UTF8_BOM) {
+        data.consume(UTF8_BOM.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{WhereVideo, WhereWatched};
+
+    #[test]
+    fn test_bom() {
+        let input = [
+            UTF8_BOM,
+            br#"[{
+                "header": "YouTube",
+                "title": "Watched An Addictive Alternative To DAWs",
+                "titleUrl": "https://www.youtube.com/watch?v\u003drtTWtzWav8I",
+                "subtitles": [{
+                    "name": "Benn Jordan",
+                    "url": "https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng"
+                }],
+                "time": "2023-06-04T04:07:59.107Z",
+                "products": ["YouTube"],
+                "activityControls": ["YouTube watch history"]
+            }]"#,
+        ]
+        .concat();
+
+        let (models, _) =
+            parser(ParserType::Json, input.as_slice(), &ParseOptions::default()).unwrap();
+
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(models.count_videos(WhereVideo::Any), 1);
+    }
+
+    #[test]
+    fn test_german_export() {
+        let input = "<html><body><div class=\"content-cell\">Angesehen\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">An Addictive Alternative To DAWs</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />04.06.2023, 06:07:59 MESZ\n</div>\n</body></html>";
+
+        let (models, _) =
+            parser(ParserType::Html, input.as_bytes(), &ParseOptions::default()).unwrap();
+
+        assert_eq!(models.count_watches(WhereWatched::Any), 1);
+        assert_eq!(
+            models.watches()[0].when.to_rfc3339(),
+            "2023-06-04T06:07:59+02:00"
+        );
+    }
+
+    #[test]
+    fn test_no_watches() {
+        let options = ParseOptions::default();
+        for (parser_type, input) in [
+            (ParserType::Html, ""),
+            (ParserType::Html, "<html><body></body></html>"),
+            (ParserType::Json, "[]"),
+        ] {
+            let (models, stats) = parser(parser_type, input.as_bytes(), &options).unwrap();
+            assert!(models.is_empty(), "{input:?}");
+            assert_eq!(stats.rows, 0);
+        }
+
+        // Still an error, unlike an empty array
+        assert!(parser(ParserType::Json, "".as_bytes(), &options).is_err());
+        assert!(parser(ParserType::Json, "[{".as_bytes(), &options).is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        let row = "Watched\u{00A0}<a href=\"https://www.youtube.com/watch?v=rtTWtzWav8I\">Title</a><br /><a href=\"https://www.youtube.com/channel/UCshObcm-nLhbu8MY50EZ5Ng\">Benn Jordan</a><br />Jun 4, 2023, 12:07:59\u{202F}AM EDT\n";
+        let input = row.repeat(3);
+
+        let mut seen = Vec::new();
+        parser_with_progress(
+            ParserType::Html,
+            input.as_bytes(),
+            &ParseOptions::default(),
+            |rows| seen.push(rows),
+        )
+        .unwrap();
+
+        assert_eq!(seen, [1, 2, 3]);
     }
 }
@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::io::{Read, Seek};
+
+use zip::ZipArchive;
+
+use super::ParserType;
+
+/// The history file names. Takeout puts them under
+/// `Takeout/YouTube and YouTube Music/history/`, but the folder names are
+/// localized, so only the file name is matched.
+const JSON_ENTRY: &str = "watch-history.json";
+const HTML_ENTRY: &str = "watch-history.html";
+
+/// Finds the watch history in a Takeout archive, preferring JSON over HTML.
+/// Returns the entry name and its parser.
+pub fn find_history_entry<R: Read + Seek>(
+    archive: &ZipArchive<R>,
+) -> Result<(String, ParserType), Box<dyn Error>> {
+    let find = |file_name: &str| {
+        archive
+            .file_names()
+            .filter(|name| name.rsplit('/').next() == Some(file_name))
+            .min()
+            .map(String::from)
+    };
+
+    if let Some(name) = find(JSON_ENTRY) {
+        Ok((name, ParserType::Json))
+    } else if let Some(name) = find(HTML_ENTRY) {
+        Ok((name, ParserType::Html))
+    } else {
+        Err(format!("No {} or {} in the archive", JSON_ENTRY, HTML_ENTRY).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn archive(names: &[&str]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in names {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"[]").unwrap();
+        }
+
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_find_history_entry() {
+        let (name, parser_type) = find_history_entry(&archive(&[
+            "Takeout/YouTube and YouTube Music/history/watch-history.html",
+            "Takeout/YouTube and YouTube Music/history/watch-history.json",
+            "Takeout/YouTube and YouTube Music/history/search-history.json",
+        ]))
+        .unwrap();
+        assert_eq!(
+            name,
+            "Takeout/YouTube and YouTube Music/history/watch-history.json"
+        );
+        assert!(matches!(parser_type, ParserType::Json));
+
+        assert!(find_history_entry(&archive(&["Takeout/archive_browser.html"])).is_err());
+    }
+}
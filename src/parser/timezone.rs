@@ -0,0 +1,130 @@
+use chrono::FixedOffset;
+
+/// How confident we are in the offset picked for a timezone abbreviation.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Confidence {
+    /// The abbreviation maps to exactly one offset.
+    Known,
+    /// The abbreviation is used by several zones, the most common one was
+    /// picked.
+    Ambiguous,
+    /// The abbreviation isn't recognized, UTC was picked.
+    Unknown,
+}
+
+const HOUR: i32 = 3600;
+
+// Abbreviations with one well known meaning
+const KNOWN: &[(&str, i32)] = &[
+    ("UTC", 0),
+    ("GMT", 0),
+    ("WET", 0),
+    ("WEST", HOUR),
+    ("CET", HOUR),
+    ("CEST", 2 * HOUR),
+    // German
+    ("MEZ", HOUR),
+    ("MESZ", 2 * HOUR),
+    ("EET", 2 * HOUR),
+    ("EEST", 3 * HOUR),
+    ("MSK", 3 * HOUR),
+    ("JST", 9 * HOUR),
+    ("KST", 9 * HOUR),
+    ("AEST", 10 * HOUR),
+    ("AEDT", 11 * HOUR),
+    ("NZST", 12 * HOUR),
+    ("NZDT", 13 * HOUR),
+    ("ADT", -3 * HOUR),
+    ("EDT", -4 * HOUR),
+    ("EST", -5 * HOUR),
+    ("CDT", -5 * HOUR),
+    ("MDT", -6 * HOUR),
+    ("MST", -7 * HOUR),
+    ("PDT", -7 * HOUR),
+    ("PST", -8 * HOUR),
+    ("AKDT", -8 * HOUR),
+    ("AKST", -9 * HOUR),
+    ("HST", -10 * HOUR),
+];
+
+// Abbreviations shared by several zones, mapped to the most likely one
+const AMBIGUOUS: &[(&str, i32)] = &[
+    // US Central, China, Cuba
+    ("CST", -6 * HOUR),
+    // India, Ireland, Israel
+    ("IST", 5 * HOUR + 30 * 60),
+    // British Summer Time, Bangladesh
+    ("BST", HOUR),
+    // Atlantic, Arabia
+    ("AST", -4 * HOUR),
+];
+
+/// Returns the offset for a timezone abbreviation like "EDT", or an explicit
+/// offset like "GMT+02:00".
+pub fn offset_for_abbreviation(abbreviation: &str) -> (FixedOffset, Confidence) {
+    if let Some(offset) = parse_explicit_offset(abbreviation) {
+        return (offset, Confidence::Known);
+    }
+
+    let lookup = |table: &[(&str, i32)]| {
+        table
+            .iter()
+            .find(|(name, _)| *name == abbreviation)
+            .map(|(_, seconds)| FixedOffset::east_opt(*seconds).unwrap())
+    };
+
+    if let Some(offset) = lookup(KNOWN) {
+        (offset, Confidence::Known)
+    } else if let Some(offset) = lookup(AMBIGUOUS) {
+        (offset, Confidence::Ambiguous)
+    } else {
+        (FixedOffset::east_opt(0).unwrap(), Confidence::Unknown)
+    }
+}
+
+/// Parses "GMT+02:00", "UTC-5" and similar.
+fn parse_explicit_offset(s: &str) -> Option<FixedOffset> {
+    let rest = s.strip_prefix("GMT").or_else(|| s.strip_prefix("UTC"))?;
+
+    let (sign, rest) = match rest.chars().next()? {
+        '+' => (1, &rest[1..]),
+        '-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+
+    FixedOffset::east_opt(sign * (hours * HOUR + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abbreviations() {
+        assert_eq!(
+            offset_for_abbreviation("EDT"),
+            (FixedOffset::west_opt(4 * HOUR).unwrap(), Confidence::Known)
+        );
+        assert_eq!(offset_for_abbreviation("CST").1, Confidence::Ambiguous);
+        assert_eq!(
+            offset_for_abbreviation("XYZ"),
+            (FixedOffset::east_opt(0).unwrap(), Confidence::Unknown)
+        );
+    }
+
+    #[test]
+    fn test_explicit_offsets() {
+        assert_eq!(
+            offset_for_abbreviation("GMT+02:00"),
+            (FixedOffset::east_opt(2 * HOUR).unwrap(), Confidence::Known)
+        );
+        assert_eq!(
+            offset_for_abbreviation("UTC-5"),
+            (FixedOffset::west_opt(5 * HOUR).unwrap(), Confidence::Known)
+        );
+    }
+}
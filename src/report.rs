@@ -0,0 +1,1027 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::model::{
+    channel_id_from_url, rank_channels, video_id_from_url, Models, Period, VideoAge, WhereVideo,
+    WhereWatched,
+};
+
+/// Number of channels listed for each year.
+const YEAR_TOP: usize = 10;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Bar heights for sparklines, lowest first.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The top channel counts the concentration line reports on.
+const CONCENTRATION_TOPS: [usize; 2] = [1, 10];
+
+/// The widest the weekly sparkline gets, longer histories put several weeks in
+/// each bar.
+const SPARKLINE_WIDTH: usize = 80;
+
+/// The longest bar in the watches per channel histogram.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Below this fraction of watches with a known offset, time based sections
+/// print a caveat.
+const LOW_TIMEZONE_COVERAGE: f64 = 0.95;
+
+/// The data behind the default report. The console output and `--format json`
+/// are both rendered from this struct so they can't drift apart.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub top_videos: Vec<VideoCount>,
+    pub top_channels: Vec<ChannelCount>,
+    pub channels_by_year: Vec<YearChannels>,
+    pub top_video_by_year: Vec<YearVideo>,
+    pub binge_days: BingeDays,
+    pub watches_per_week: WeeklyWatches,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub videos_per_channel: Option<Vec<ChannelVideos>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watches_by_period: Option<PeriodCounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hour_profile: Option<Vec<WeekdayHours>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_histogram: Option<Vec<ChannelBin>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_age: Option<VideoAges>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_time: Option<WatchTime>,
+    /// Set with `--page`, `top_videos` and `top_channels` then only hold that
+    /// page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
+}
+
+/// The single number stats at the top of the report, which `--summary-only`
+/// prints alone.
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub unique_videos: u64,
+    pub watches: u64,
+    /// The local dates of the first and last watch.
+    pub first_watch: Option<NaiveDate>,
+    pub last_watch: Option<NaiveDate>,
+    pub shorts_watches: usize,
+    pub timezone_coverage: f64,
+    /// Dates and hours are UTC's rather than each watch's own offset's, see
+    /// `Models::use_utc`.
+    pub utc: bool,
+    /// The share of watches from the top 1 and top 10 channels.
+    pub concentration: Vec<Concentration>,
+    pub weekend_split: WeekendSplit,
+}
+
+/// A 1-based page of the top lists.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub struct Page {
+    pub number: usize,
+    pub size: usize,
+}
+
+impl Page {
+    /// The range of a `total` long list that falls on this page. Empty when
+    /// the page is past the end.
+    fn range(&self, total: usize) -> std::ops::Range<usize> {
+        // Huge --page and --page-size values would overflow
+        let start = (self.number - 1).saturating_mul(self.size).min(total);
+        let end = start.saturating_add(self.size).min(total);
+        start..end
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PageInfo {
+    #[serde(flatten)]
+    pub page: Page,
+    /// Offset of the first entry shown, in the full list.
+    pub videos_start: usize,
+    pub videos_total: usize,
+    pub channels_start: usize,
+    pub channels_total: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VideoCount {
+    pub count: usize,
+    pub title: String,
+    pub url: String,
+    pub channel_name: String,
+    pub channel_url: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChannelCount {
+    pub count: usize,
+    pub name: String,
+    pub url: String,
+    /// The fraction of this channel's watches on a weekend, only set in
+    /// `top_channels`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekend_share: Option<f64>,
+}
+
+/// A top channel with its own top videos.
+#[derive(Serialize, Debug)]
+pub struct ChannelVideos {
+    #[serde(flatten)]
+    pub channel: ChannelCount,
+    pub videos: Vec<VideoCount>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct YearChannels {
+    pub year: i32,
+    /// Different videos watched that year, however many times each.
+    pub unique_videos: usize,
+    pub unique_channels: usize,
+    pub channels: Vec<ChannelCount>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct YearVideo {
+    pub year: i32,
+    pub video: VideoCount,
+}
+
+/// Days with far more watches than usual, see `Models::outlier_days`.
+#[derive(Serialize, Debug)]
+pub struct BingeDays {
+    /// Standard deviations above the mean a day needs to be listed.
+    pub k: f64,
+    /// Mean watches per day.
+    pub mean: f64,
+    pub days: Vec<BingeDay>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BingeDay {
+    pub date: NaiveDate,
+    pub count: usize,
+    /// The most watched channel that day, with its count that day.
+    pub channel: ChannelCount,
+}
+
+/// The fraction of watches, from 0 to 1, from the `top` most watched channels.
+#[derive(Serialize, Debug)]
+pub struct Concentration {
+    pub top: usize,
+    pub share: f64,
+}
+
+/// Watches on weekdays and on weekends, by each watch's local date.
+#[derive(Serialize, Debug)]
+pub struct WeekendSplit {
+    pub weekday: usize,
+    pub weekend: usize,
+}
+
+/// The fraction of a weekday's watches in each local hour.
+#[derive(Serialize, Debug)]
+pub struct WeekdayHours {
+    pub weekday: String,
+    pub hours: [f64; 24],
+}
+
+/// The number of channels watched between `min` and `max` times, inclusive.
+#[derive(Serialize, Debug)]
+pub struct ChannelBin {
+    pub min: usize,
+    /// Unset for the last bin, which has no upper end.
+    pub max: Option<usize>,
+    pub channels: usize,
+}
+
+impl ChannelBin {
+    /// "1", "2-5" or "51+".
+    fn label(&self) -> String {
+        match self.max {
+            Some(max) if max == self.min => max.to_string(),
+            Some(max) => format!("{}-{}", self.min, max),
+            None => format!("{}+", self.min),
+        }
+    }
+}
+
+/// Watches by how long after upload the video was watched.
+#[derive(Serialize, Debug)]
+pub struct VideoAges {
+    pub counts: Vec<VideoAgeCount>,
+    /// Watches of videos without a known upload date, which aren't in
+    /// `counts`.
+    pub unknown: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VideoAgeCount {
+    pub age: VideoAge,
+    pub count: usize,
+}
+
+/// The durations of the watched videos added up, as if each was watched to
+/// the end.
+#[derive(Serialize, Debug)]
+pub struct WatchTime {
+    pub seconds: u64,
+    /// Watches of videos without a known duration, which aren't in `seconds`.
+    pub unknown: usize,
+}
+
+/// Watches in every week from the first watch to the last, including weeks
+/// without any.
+#[derive(Serialize, Debug)]
+pub struct WeeklyWatches {
+    /// The label of the first week, see `Period::label`.
+    pub first_week: Option<String>,
+    pub counts: Vec<usize>,
+}
+
+impl WeeklyWatches {
+    fn new(models: &Models) -> WeeklyWatches {
+        let counts = models.count_watches_by_period(Period::Week);
+        let dates = models.watches().iter().map(|watched| watched.local_date());
+        let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+            return WeeklyWatches {
+                first_week: None,
+                counts: Vec::new(),
+            };
+        };
+
+        let mut week = first - Duration::days(first.weekday().num_days_from_monday().into());
+        let mut weekly = Vec::new();
+        while week <= last {
+            let label = Period::Week.label(week);
+            weekly.push(counts.get(&label).copied().unwrap_or(0));
+            week += Duration::days(7);
+        }
+
+        WeeklyWatches {
+            first_week: Some(Period::Week.label(first)),
+            counts: weekly,
+        }
+    }
+}
+
+/// What `Report::new` includes.
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Entries in the top video and channel lists, unless they're paged.
+    pub top: usize,
+    /// Only keep this slice of the full top lists.
+    pub page: Option<Page>,
+    /// Also list this many videos for each of the `top` channels.
+    pub videos_per_channel: Option<usize>,
+    pub group_by: Option<Period>,
+    pub hour_profile: bool,
+    /// The upper ends of the watches per channel histogram's bins, when it's
+    /// included.
+    pub channel_histogram: Option<Vec<usize>>,
+    /// The days counted as the weekend.
+    pub weekend: Vec<Weekday>,
+    /// Leave channels with fewer watches in a year out of that year, and
+    /// years without any channels left out entirely.
+    pub min_year_watches: usize,
+    /// Standard deviations above the daily mean for a binge day.
+    pub outlier_k: f64,
+    /// Only marks the report, the models must already be in UTC.
+    pub utc: bool,
+    /// Upload dates by video ID or URL, for the video age section.
+    pub upload_dates: Option<HashMap<String, NaiveDate>>,
+    /// Video durations in seconds by video ID or URL, for the watch time
+    /// section.
+    pub durations: Option<HashMap<String, u64>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PeriodCounts {
+    pub period: Period,
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl Report {
+    /// Builds the report, see `ReportOptions` for what it includes.
+    pub fn new(models: &Models, options: &ReportOptions) -> Report {
+        let top = options.top;
+        let weekend = options.weekend.as_slice();
+        // With --page the lists are paged through instead of cut at `top`
+        let listed = if options.page.is_some() {
+            usize::MAX
+        } else {
+            top
+        };
+
+        let mut top_videos: Vec<VideoCount> = models
+            .top_videos(listed)
+            .into_iter()
+            .map(|(count, video)| VideoCount {
+                count,
+                title: video.title.clone(),
+                url: video.url.clone(),
+                channel_name: video.channel.name.clone(),
+                channel_url: video.channel.url.clone(),
+            })
+            .collect();
+
+        let weekend_by_channel = models.weekend_weekday_split_by_channel(weekend);
+        let mut top_channels: Vec<ChannelCount> = models
+            .top_channels(listed)
+            .into_iter()
+            .map(|(count, channel)| ChannelCount {
+                count,
+                name: channel.name.clone(),
+                url: channel.url.clone(),
+                weekend_share: weekend_by_channel
+                    .get(&channel.url)
+                    .map(|&(_, weekend)| weekend as f64 / count as f64),
+            })
+            .collect();
+
+        let videos_per_channel = options.videos_per_channel.map(|videos| {
+            models
+                .top_videos_per_channel(top, videos)
+                .into_iter()
+                .map(|(count, channel, videos)| ChannelVideos {
+                    channel: ChannelCount {
+                        count,
+                        name: channel.name.clone(),
+                        url: channel.url.clone(),
+                        weekend_share: None,
+                    },
+                    videos: videos
+                        .into_iter()
+                        .map(|(count, video)| VideoCount {
+                            count,
+                            title: video.title.clone(),
+                            url: video.url.clone(),
+                            channel_name: video.channel.name.clone(),
+                            channel_url: video.channel.url.clone(),
+                        })
+                        .collect(),
+                })
+                .collect()
+        });
+
+        let page = options.page.map(|page| {
+            let videos = page.range(top_videos.len());
+            let channels = page.range(top_channels.len());
+            let info = PageInfo {
+                page,
+                videos_start: videos.start,
+                videos_total: top_videos.len(),
+                channels_start: channels.start,
+                channels_total: top_channels.len(),
+            };
+
+            top_videos = top_videos.drain(videos).collect();
+            top_channels = top_channels.drain(channels).collect();
+            info
+        });
+
+        let channel_watches_by_year = models.count_watched_by_channel_by_year();
+        let mut channel_watches_by_year = channel_watches_by_year.iter().collect::<Vec<_>>();
+        channel_watches_by_year.sort_by_key(|(year, _)| **year);
+
+        let unique_videos_by_year = models.unique_videos_by_year();
+        let unique_channels_by_year = models.unique_channels_by_year();
+        let channels_by_year = channel_watches_by_year
+            .into_iter()
+            .filter_map(|(year, channel_watches)| {
+                let mut channel_watches = channel_watches
+                    .values()
+                    .filter(|(count, _)| *count >= options.min_year_watches)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if channel_watches.is_empty() {
+                    return None;
+                }
+                rank_channels(&mut channel_watches);
+
+                Some(YearChannels {
+                    year: *year,
+                    unique_videos: unique_videos_by_year[year],
+                    unique_channels: unique_channels_by_year[year],
+                    channels: channel_watches
+                        .into_iter()
+                        .take(YEAR_TOP)
+                        .map(|(count, channel)| ChannelCount {
+                            count,
+                            name: channel.name.clone(),
+                            url: channel.url.clone(),
+                            weekend_share: None,
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+
+        let top_video_by_year = models
+            .top_video_by_year()
+            .into_iter()
+            .map(|(year, (count, video))| YearVideo {
+                year,
+                video: VideoCount {
+                    count,
+                    title: video.title.clone(),
+                    url: video.url.clone(),
+                    channel_name: video.channel.name.clone(),
+                    channel_url: video.channel.url.clone(),
+                },
+            })
+            .collect();
+
+        let (mean, outliers) = models.outlier_days(options.outlier_k);
+        let binge_days = BingeDays {
+            k: options.outlier_k,
+            mean,
+            days: outliers
+                .into_iter()
+                .map(|(date, count, (channel_count, channel))| BingeDay {
+                    date,
+                    count,
+                    channel: ChannelCount {
+                        count: channel_count,
+                        name: channel.name.clone(),
+                        url: channel.url.clone(),
+                        weekend_share: None,
+                    },
+                })
+                .collect(),
+        };
+
+        Report {
+            summary: Summary::new(models, options),
+            top_videos,
+            top_channels,
+            channels_by_year,
+            top_video_by_year,
+            binge_days,
+            watches_per_week: WeeklyWatches::new(models),
+            videos_per_channel,
+            watches_by_period: options.group_by.map(|period| PeriodCounts {
+                period,
+                counts: models.count_watches_by_period(period),
+            }),
+            hour_profile: options.hour_profile.then(|| {
+                models
+                    .hour_profile_by_weekday()
+                    .into_iter()
+                    .zip(WEEKDAYS)
+                    .map(|(hours, weekday)| WeekdayHours {
+                        weekday: weekday.to_string(),
+                        hours,
+                    })
+                    .collect()
+            }),
+            channel_histogram: options.channel_histogram.as_ref().map(|bins| {
+                let mins = [0].iter().chain(bins).map(|max| max + 1);
+                let maxes = bins.iter().copied().map(Some).chain([None]);
+                mins.zip(maxes)
+                    .zip(models.count_channels_by_watches(bins))
+                    .map(|((min, max), channels)| ChannelBin { min, max, channels })
+                    .collect()
+            }),
+            video_age: options.upload_dates.as_ref().map(|upload_dates| {
+                let (counts, unknown) = models.count_watches_by_video_age(upload_dates);
+                VideoAges {
+                    counts: VideoAge::ALL
+                        .into_iter()
+                        .zip(counts)
+                        .map(|(age, count)| VideoAgeCount { age, count })
+                        .collect(),
+                    unknown,
+                }
+            }),
+            watch_time: options.durations.as_ref().map(|durations| {
+                let (seconds, unknown) = models.sum_watch_time(durations);
+                WatchTime { seconds, unknown }
+            }),
+            page,
+        }
+    }
+
+    /// Replaces video and channel URLs with their IDs, where they contain one.
+    pub fn use_ids(&mut self) {
+        let video_id = |url: &mut String| {
+            if let Some(id) = video_id_from_url(url) {
+                *url = id.to_string();
+            }
+        };
+        let channel_id = |url: &mut String| {
+            if let Some(id) = channel_id_from_url(url) {
+                *url = id.to_string();
+            }
+        };
+
+        for video in self.top_videos.iter_mut() {
+            video_id(&mut video.url);
+            channel_id(&mut video.channel_url);
+        }
+        for channel in self.top_channels.iter_mut() {
+            channel_id(&mut channel.url);
+        }
+        for year in self.channels_by_year.iter_mut() {
+            for channel in year.channels.iter_mut() {
+                channel_id(&mut channel.url);
+            }
+        }
+        for year in self.top_video_by_year.iter_mut() {
+            video_id(&mut year.video.url);
+            channel_id(&mut year.video.channel_url);
+        }
+        for day in self.binge_days.days.iter_mut() {
+            channel_id(&mut day.channel.url);
+        }
+        for channel in self.videos_per_channel.iter_mut().flatten() {
+            channel_id(&mut channel.channel.url);
+            for video in channel.videos.iter_mut() {
+                video_id(&mut video.url);
+                channel_id(&mut video.channel_url);
+            }
+        }
+    }
+
+    pub fn print(&self) {
+        self.summary.print();
+        self.print_top_lists();
+
+        if let Some(channels) = &self.videos_per_channel {
+            println!();
+            println!(
+                "{} {} {}",
+                "Top videos from the".bold(),
+                format!("{}", channels.len()).bold(),
+                "most watched channels".bold()
+            );
+            for (i, channel) in channels.iter().enumerate() {
+                print_ranked(i, &channel.channel.name, channel.channel.count, None);
+                for video in channel.videos.iter() {
+                    println!(
+                        "       {} {}",
+                        video.title,
+                        format!("({})", video.count).dimmed()
+                    );
+                }
+            }
+        }
+
+        println!();
+        println!("{}", "Top channel views by year".bold());
+
+        for year in self.channels_by_year.iter() {
+            print!(
+                "{} {}: ",
+                year.year,
+                format!(
+                    "({} videos, {} channels)",
+                    year.unique_videos, year.unique_channels
+                )
+                .dimmed()
+            );
+
+            for channel in year.channels.iter() {
+                print!(
+                    "{} {} ",
+                    channel.name,
+                    format!("({})", channel.count).dimmed()
+                );
+            }
+
+            println!();
+        }
+
+        println!();
+        println!("{}", "Most watched video by year".bold());
+
+        for year in self.top_video_by_year.iter() {
+            let s = if year.video.count != 1 { "s" } else { "" };
+            println!(
+                "{}: {} {}",
+                year.year,
+                year.video.title,
+                format!("({} time{})", year.video.count, s).dimmed()
+            );
+        }
+
+        let binge = &self.binge_days;
+        println!();
+        println!(
+            "{} {}",
+            "Binge days".bold(),
+            format!(
+                "(over {} standard deviations above the mean of {:.1} a day)",
+                binge.k, binge.mean
+            )
+            .dimmed()
+        );
+        self.print_timezone_caveat();
+        if binge.days.is_empty() {
+            println!("  {}", "None".dimmed());
+        }
+        for day in binge.days.iter() {
+            println!(
+                "  {} {} {} {} {}",
+                day.date,
+                day.count,
+                "watches, mostly".dimmed(),
+                day.channel.name,
+                format!("({})", day.channel.count).dimmed()
+            );
+        }
+
+        if let Some(first_week) = &self.watches_per_week.first_week {
+            let counts = &self.watches_per_week.counts;
+            let weeks_per_bar = counts.len().div_ceil(SPARKLINE_WIDTH);
+            let bars = counts
+                .chunks(weeks_per_bar)
+                .map(|chunk| chunk.iter().sum::<usize>() as f64)
+                .collect::<Vec<_>>();
+
+            println!();
+            println!(
+                "{} {}",
+                "Watches per week".bold(),
+                format!("(from {}, {} weeks per bar)", first_week, weeks_per_bar).dimmed()
+            );
+            println!("  {}", sparkline(&bars));
+        }
+
+        if let Some(PeriodCounts { period, counts }) = &self.watches_by_period {
+            println!();
+            println!("{} {:?}", "Watches by".bold(), period);
+            self.print_timezone_caveat();
+
+            for (label, count) in counts {
+                println!("  {} {}", label, count);
+            }
+        }
+
+        if let Some(profile) = &self.hour_profile {
+            println!();
+            println!("{}", "Hour of day by weekday".bold());
+            self.print_timezone_caveat();
+            println!("      {}", "0     6     12    18   ".dimmed());
+
+            for WeekdayHours { weekday, hours } in profile {
+                // Scaled per weekday, so each row shows its own shape
+                println!("  {}  {}", weekday, sparkline(hours));
+            }
+        }
+
+        if let Some(bins) = &self.channel_histogram {
+            println!();
+            println!("{}", "Channels by number of watches".bold());
+
+            let most = bins.iter().map(|bin| bin.channels).max().unwrap_or(0);
+            let labels = bins.iter().map(ChannelBin::label).collect::<Vec<_>>();
+            let width = labels.iter().map(String::len).max().unwrap_or(0);
+            for (bin, label) in bins.iter().zip(labels) {
+                // Any channels at all get at least one block
+                let len = (bin.channels * HISTOGRAM_WIDTH).div_ceil(most.max(1));
+                println!(
+                    "  {:>width$} {} {}",
+                    label,
+                    BARS[BARS.len() - 1].to_string().repeat(len),
+                    bin.channels.to_string().dimmed()
+                );
+            }
+        }
+
+        if let Some(VideoAges { counts, unknown }) = &self.video_age {
+            let known = counts.iter().map(|age| age.count).sum::<usize>();
+            println!();
+            println!(
+                "{} {}",
+                "Video age when watched".bold(),
+                format!(
+                    "(upload date known for {:.0}% of watches)",
+                    percent(known, (known + unknown) as u64)
+                )
+                .dimmed()
+            );
+
+            for VideoAgeCount { age, count } in counts {
+                println!(
+                    "  {:<14} {:>3.0}%",
+                    age.label(),
+                    percent(*count, known as u64)
+                );
+            }
+        }
+
+        if let Some(WatchTime { seconds, unknown }) = &self.watch_time {
+            let known = self.summary.watches as usize - unknown;
+            println!();
+            println!(
+                "{} {}h {}m {}",
+                "Watch time".bold(),
+                seconds / 3600,
+                seconds % 3600 / 60,
+                format!(
+                    "(duration known for {:.0}% of watches)",
+                    percent(known, self.summary.watches)
+                )
+                .dimmed()
+            );
+        }
+    }
+
+    /// Prints the top video and channel lists, the part of the report
+    /// `--period` repeats for each period.
+    pub fn print_top_lists(&self) {
+        let (videos_start, channels_start) = self
+            .page
+            .as_ref()
+            .map_or((0, 0), |page| (page.videos_start, page.channels_start));
+
+        println!();
+        print_ranked_heading(
+            "videos",
+            self.top_videos.len(),
+            videos_start,
+            self.page.as_ref().map(|page| page.videos_total),
+        );
+        for (i, video) in self.top_videos.iter().enumerate() {
+            print_ranked(videos_start + i, &video.title, video.count, None);
+        }
+
+        println!();
+        print_ranked_heading(
+            "channels",
+            self.top_channels.len(),
+            channels_start,
+            self.page.as_ref().map(|page| page.channels_total),
+        );
+        for (i, channel) in self.top_channels.iter().enumerate() {
+            let weekend = channel
+                .weekend_share
+                .map(|share| format!("({:.0}% on weekends)", share * 100.0));
+            print_ranked(
+                channels_start + i,
+                &channel.name,
+                channel.count,
+                weekend.as_deref(),
+            );
+        }
+    }
+
+    fn print_timezone_caveat(&self) {
+        if self.summary.timezone_coverage < LOW_TIMEZONE_COVERAGE {
+            println!(
+                "{}",
+                format!(
+                    "Note: only {:.0}% of watches have an unambiguous timezone, times may be off",
+                    self.summary.timezone_coverage * 100.0
+                )
+                .yellow()
+            );
+        }
+    }
+}
+
+impl Summary {
+    /// Builds the summary, only `options.weekend` and `options.utc` apply.
+    pub fn new(models: &Models, options: &ReportOptions) -> Summary {
+        let weekend = options.weekend.as_slice();
+        let dates = models.watches().iter().map(|watched| watched.local_date());
+
+        Summary {
+            unique_videos: models.count_videos(WhereVideo::Any),
+            watches: models.count_watches(WhereWatched::Any),
+            shorts_watches: models
+                .watches()
+                .iter()
+                .filter(|watched| watched.video.is_short())
+                .count(),
+            first_watch: dates.clone().min(),
+            last_watch: dates.max(),
+            timezone_coverage: models.timezone_coverage(),
+            utc: options.utc,
+            concentration: models
+                .channel_concentration(&CONCENTRATION_TOPS)
+                .into_iter()
+                .map(|(top, share)| Concentration { top, share })
+                .collect(),
+            weekend_split: {
+                let (weekday, weekend) = models.weekend_weekday_split(weekend);
+                WeekendSplit { weekday, weekend }
+            },
+        }
+    }
+
+    pub fn print(&self) {
+        print_totals(self.unique_videos, self.watches);
+        if let (Some(first), Some(last)) = (self.first_watch, self.last_watch) {
+            println!(
+                "{} {} {} {} {}",
+                "Span:".dimmed(),
+                first,
+                "to".dimmed(),
+                last,
+                format!("({} days)", (last - first).num_days() + 1).dimmed()
+            );
+        }
+        if self.utc {
+            println!(
+                "{}",
+                "Dates, hours and weekdays are in UTC, not the local time of each watch".yellow()
+            );
+        }
+        println!(
+            "{} {} {}",
+            "Shorts:".dimmed(),
+            self.shorts_watches,
+            format!(
+                "watches ({:.0}%)",
+                percent(self.shorts_watches, self.watches)
+            )
+            .dimmed(),
+        );
+        let concentration = self
+            .concentration
+            .iter()
+            .map(|c| format!("top {}: {:.0}%", c.top, c.share * 100.0))
+            .collect::<Vec<_>>();
+        println!("{} {}", "Concentration:".dimmed(), concentration.join(", "));
+        let split = &self.weekend_split;
+        let total = split.weekday + split.weekend;
+        println!(
+            "{} {:.0}% / {} {:.0}%",
+            "Weekday:".dimmed(),
+            percent(split.weekday, total as u64),
+            "Weekend:".dimmed(),
+            percent(split.weekend, total as u64),
+        );
+    }
+}
+
+/// Draws `values` as bars scaled to the largest one, with a space for zero.
+fn sparkline(values: &[f64]) -> String {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+
+    values
+        .iter()
+        .map(|value| {
+            if *value == 0.0 {
+                ' '
+            } else {
+                let i = (value / max * (BARS.len() - 1) as f64).round();
+                BARS[i as usize]
+            }
+        })
+        .collect()
+}
+
+/// Prints the "History contains ..." line, shared with `--count-only`.
+pub fn print_totals(unique_videos: u64, watches: u64) {
+    println!(
+        "{} {} {} {} {}",
+        "History contains".dimmed(),
+        unique_videos,
+        "unique videos and".dimmed(),
+        watches,
+        "watches".dimmed(),
+    );
+}
+
+fn percent(part: usize, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+/// Prints "Top 50 most watched videos", or with `--page`, "Most watched
+/// videos (showing 101–200 of 1000)".
+fn print_ranked_heading(kind: &str, len: usize, start: usize, total: Option<usize>) {
+    match total {
+        None => println!(
+            "{} {} {}",
+            "Top".bold(),
+            format!("{}", len).bold(),
+            format!("most watched {}", kind).bold()
+        ),
+        Some(total) => println!(
+            "{} {}",
+            format!("Most watched {}", kind).bold(),
+            if len == 0 {
+                format!("(showing 0 of {})", total)
+            } else {
+                format!("(showing {}–{} of {})", start + 1, start + len, total)
+            }
+            .dimmed()
+        ),
+    }
+}
+
+fn print_ranked(i: usize, title: &str, count: usize, note: Option<&str>) {
+    let s = if count != 1 { "s" } else { "" };
+
+    println!(
+        "  {index}. {title} {viewed} {count} {time}{s}{note}",
+        index = i + 1,
+        title = title,
+        viewed = "viewed".dimmed(),
+        count = count,
+        time = "time".dimmed(),
+        s = s.dimmed(),
+        note = note
+            .map(|note| format!(" {}", note))
+            .unwrap_or_default()
+            .dimmed(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::WhereChannel;
+
+    #[test]
+    fn test_weekly_watches_fills_gaps() {
+        let mut models = Models::new();
+        let channel = models.insert_channel("c".into(), "C".into());
+        let video = models.insert_video("v".into(), "V".into(), WhereChannel::Reference(channel));
+        // A Sunday, then the Monday after, then three weeks later
+        for when in [
+            "2023-01-01T10:00:00Z",
+            "2023-01-02T10:00:00Z",
+            "2023-01-23T10:00:00Z",
+        ] {
+            let when = chrono::DateTime::parse_from_rfc3339(when).unwrap();
+            models.insert_watched(when, WhereVideo::Reference(video.clone()));
+        }
+
+        let weekly = WeeklyWatches::new(&models);
+
+        assert_eq!(weekly.first_week.as_deref(), Some("2022-W52"));
+        assert_eq!(weekly.counts, [1, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_page_range() {
+        let page = |number, size| Page { number, size };
+
+        assert_eq!(page(1, 20).range(50), 0..20);
+        assert_eq!(page(3, 20).range(50), 40..50);
+        assert_eq!(page(4, 20).range(50), 50..50);
+        assert_eq!(page(usize::MAX, usize::MAX).range(50), 50..50);
+        assert_eq!(page(1, usize::MAX).range(50), 0..50);
+    }
+
+    #[test]
+    fn test_min_year_watches() {
+        let mut models = Models::new();
+        let a = models.insert_channel("a".into(), "A".into());
+        let b = models.insert_channel("b".into(), "B".into());
+        let video_a = models.insert_video("1".into(), "1".into(), WhereChannel::Reference(a));
+        let video_b = models.insert_video("2".into(), "2".into(), WhereChannel::Reference(b));
+
+        for (when, video) in [
+            ("2021-06-01T10:00:00Z", &video_a),
+            ("2022-06-01T10:00:00Z", &video_a),
+            ("2022-06-02T10:00:00Z", &video_a),
+            ("2022-06-03T10:00:00Z", &video_b),
+        ] {
+            let when = chrono::DateTime::parse_from_rfc3339(when).unwrap();
+            models.insert_watched(when, WhereVideo::Reference(video.clone()));
+        }
+
+        let report = Report::new(
+            &models,
+            &ReportOptions {
+                top: 10,
+                page: None,
+                videos_per_channel: None,
+                group_by: None,
+                hour_profile: false,
+                channel_histogram: None,
+                weekend: vec![Weekday::Sat, Weekday::Sun],
+                min_year_watches: 2,
+                outlier_k: 3.0,
+                utc: false,
+                upload_dates: None,
+                durations: None,
+            },
+        );
+
+        // 2021 only had a single watch of A
+        let years = report
+            .channels_by_year
+            .iter()
+            .map(|year| {
+                let names = year.channels.iter().map(|c| c.name.as_str());
+                (year.year, names.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(years, [(2022, vec!["A"])]);
+    }
+}
@@ -23,13 +23,57 @@ where
     R: Read,
 {
     bytes_read: usize,
+    encoding: Encoding,
     reader: R,
     buf: [u8; BUFFER_SIZE],
     chars: Vec<char>,
     buf_len: usize,
 }
 
+/// How bytes that aren't valid UTF-8 are handled.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    /// Report them as `Error::InvalidBytes`.
+    #[default]
+    Utf8,
+    /// Decode each one as Windows-1252, for old exports that aren't clean
+    /// UTF-8.
+    Windows1252Fallback,
+    /// Replace each invalid sequence with U+FFFD, like
+    /// `String::from_utf8_lossy`.
+    Lossy,
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "windows-1252" | "cp1252" | "latin1" => Ok(Encoding::Windows1252Fallback),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Windows-1252 differs from Latin-1 only in 0x80 to 0x9F. The five bytes it
+/// leaves undefined map to the control characters of the same value, like
+/// browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Decodes a single Windows-1252 byte.
+pub fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     /// An invalid UTF-8 character was read. Calling next() again skips the
     /// invalid bytes.
@@ -64,9 +108,16 @@ impl PartialEq<Error> for Error {
 }
 
 impl<R: Read> Utf8Iter<R> {
+    // Called by the tests and the fuzz targets
+    #[allow(dead_code)]
     pub fn new(reader: R) -> Utf8Iter<R> {
+        Self::with_encoding(reader, Encoding::Utf8)
+    }
+
+    pub fn with_encoding(reader: R, encoding: Encoding) -> Utf8Iter<R> {
         Utf8Iter {
             bytes_read: 0,
+            encoding,
             reader,
             buf: [0; BUFFER_SIZE],
             chars: Vec::with_capacity(BUFFER_SIZE),
@@ -74,6 +125,12 @@ impl<R: Read> Utf8Iter<R> {
         }
     }
 
+    /// Bytes read from the underlying reader so far. Bytes still buffered
+    /// count, even if they haven't been decoded yet.
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
     /// Returns the next character in the stream, possibly made of several
     /// bytes, from the underlying reader.
     ///
@@ -89,11 +146,15 @@ impl<R: Read> Utf8Iter<R> {
 
         // Loop to call read() until a full UTF-8 character is read.
         loop {
+            let mut end = false;
+
             match self.reader.read(self.buf[self.buf_len..].as_mut()) {
                 Ok(0) => {
                     if self.buf_len == 0 {
                         return Err(Error::End);
                     }
+
+                    end = true;
                 }
                 Ok(n) => {
                     self.buf_len += n;
@@ -133,7 +194,7 @@ impl<R: Read> Utf8Iter<R> {
 
                         let bytes_consumed = s.len();
                         self.buf.copy_within(bytes_consumed.., 0);
-                        self.buf_len = self.buf_len - bytes_consumed;
+                        self.buf_len -= bytes_consumed;
 
                         // The invalid bytes are kept in the buffer so we can
                         // return them in an InvalidBytes error when next() is
@@ -148,9 +209,18 @@ impl<R: Read> Utf8Iter<R> {
                             let invalid_bytes = Vec::from(&self.buf[..n]);
 
                             self.buf.copy_within(n.., 0);
-                            self.buf_len = self.buf_len - n;
+                            self.buf_len -= n;
 
-                            return Err(Error::InvalidBytes(invalid_bytes));
+                            return self.invalid(invalid_bytes);
+                        }
+                        None if end => {
+                            // The reader ended partway through a code point.
+                            // Report it rather than waiting for bytes that
+                            // may never come.
+                            let invalid_bytes = Vec::from(&self.buf[..self.buf_len]);
+                            self.buf_len = 0;
+
+                            return self.invalid(invalid_bytes);
                         }
                         None => {
                             // A partial code point was read, read more bytes.
@@ -161,6 +231,20 @@ impl<R: Read> Utf8Iter<R> {
             };
         }
     }
+
+    /// Handles bytes that aren't valid UTF-8, according to the encoding.
+    fn invalid(&mut self, bytes: Vec<u8>) -> Result<char, Error> {
+        match self.encoding {
+            Encoding::Utf8 => Err(Error::InvalidBytes(bytes)),
+            Encoding::Windows1252Fallback => {
+                for &byte in bytes.iter().rev() {
+                    self.chars.push(windows_1252(byte));
+                }
+                Ok(self.chars.pop().unwrap())
+            }
+            Encoding::Lossy => Ok(char::REPLACEMENT_CHARACTER),
+        }
+    }
 }
 
 impl<R: Read> Iterator for Utf8Iter<R> {
@@ -243,4 +327,36 @@ mod tests {
         assert_eq!(iter.next(), Err(Error::InvalidBytes(vec![0xC0])));
         assert_eq!(iter.next(), Err(Error::End));
     }
+
+    #[test]
+    fn test_windows_1252_fallback() {
+        // "Café – 50€" in Windows-1252, then a valid UTF-8 "é"
+        let bytes = b"Caf\xE9 \x96 50\x80 \xC3\xA9".to_vec();
+
+        let iter = Utf8Iter::with_encoding(bytes.as_slice(), Encoding::Windows1252Fallback);
+        let decoded = iter.collect::<Result<String, _>>().unwrap();
+        assert_eq!(decoded, "Café – 50€ é");
+
+        let mut iter = Utf8Iter::new(bytes.as_slice());
+        assert!(iter.any(|c| c == Err(Error::InvalidBytes(vec![0xE9]))));
+    }
+
+    #[test]
+    fn test_lossy() {
+        let bytes = b"a\xE9b\xE4\xB8c\xE4".to_vec();
+
+        let iter = Utf8Iter::with_encoding(bytes.as_slice(), Encoding::Lossy);
+        let decoded = iter.collect::<Result<String, _>>().unwrap();
+        assert_eq!(decoded, String::from_utf8_lossy(&bytes));
+    }
+
+    #[test]
+    fn test_truncated_code_point_reader() {
+        let mut reader = std::io::Cursor::new(vec![0x61, 0xE4]);
+        let mut iter = Utf8Iter::new(&mut reader);
+
+        assert_eq!(iter.next(), Ok('a'));
+        assert_eq!(iter.next(), Err(Error::InvalidBytes(vec![0xE4])));
+        assert_eq!(iter.next(), Err(Error::End));
+    }
 }
@@ -0,0 +1,187 @@
+//! Writes YAML for `--format yaml`. There's no YAML crate in the tree and the
+//! output only needs block mappings, sequences and scalars, so values go
+//! through `serde_json::Value` and are written by hand. serde_json's
+//! `preserve_order` keeps the keys in field order, like the JSON and TOML
+//! output. Strings are double quoted with JSON's escapes, which YAML reads the
+//! same way.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Spaces per nesting level.
+const INDENT: usize = 2;
+
+/// Plain keys YAML 1.1 readers would take as a boolean or null.
+const RESERVED: [&str; 9] = ["true", "false", "yes", "no", "on", "off", "y", "n", "null"];
+
+pub fn to_string<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    let mut out = String::new();
+    write_block(&mut out, &serde_json::to_value(value)?, 0);
+
+    Ok(out)
+}
+
+/// Writes `value` from the start of a line, indented by `indent` spaces.
+fn write_block(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                out.push_str(&" ".repeat(indent));
+                out.push_str(&key_text(key));
+                out.push(':');
+                write_entry(out, value, indent);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                out.push_str(&" ".repeat(indent));
+                out.push('-');
+                write_entry(out, item, indent);
+            }
+        }
+        scalar => {
+            out.push_str(&" ".repeat(indent));
+            out.push_str(&scalar_text(scalar));
+            out.push('\n');
+        }
+    }
+}
+
+/// Writes the value after a `key:` or `-`, on the same line if it's a scalar
+/// and nested on the lines below if not.
+fn write_entry(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_block(out, value, indent + INDENT);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_block(out, value, indent + INDENT);
+        }
+        scalar => {
+            out.push(' ');
+            out.push_str(&scalar_text(scalar));
+            out.push('\n');
+        }
+    }
+}
+
+/// A scalar, or an empty collection in flow style.
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::Object(_) => "{}".into(),
+        Value::Array(_) => "[]".into(),
+        // Numbers, booleans and null are spelled the same as in JSON, and
+        // a JSON string is a valid double quoted YAML string
+        value => value.to_string(),
+    }
+}
+
+/// Keys are left plain when they can't be read as anything but a string.
+fn key_text(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&key.to_ascii_lowercase().as_str());
+
+    if plain {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_string() {
+        let value = serde_json::json!({
+            "watches": 3,
+            "title": "Say \"hi\"\n",
+            "first_watch": null,
+            "2023": { "on": true },
+            "top": [{ "count": 2, "tags": ["a"] }, { "count": 1, "tags": [] }],
+            "empty": {},
+        });
+
+        assert_eq!(
+            to_string(&value).unwrap(),
+            [
+                "watches: 3",
+                "title: \"Say \\\"hi\\\"\\n\"",
+                "first_watch: null",
+                "\"2023\":",
+                "  \"on\": true",
+                "top:",
+                "  -",
+                "    count: 2",
+                "    tags:",
+                "      - \"a\"",
+                "  -",
+                "    count: 1",
+                "    tags: []",
+                "empty: {}",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(to_string(&"top level").unwrap(), "\"top level\"\n");
+    }
+
+    #[test]
+    fn test_field_order() {
+        #[derive(Serialize)]
+        struct Summary {
+            watches: u32,
+            channels: u32,
+            average: f64,
+        }
+
+        let summary = Summary {
+            watches: 3,
+            channels: 1,
+            average: 1.5,
+        };
+        assert_eq!(
+            to_string(&summary).unwrap(),
+            "watches: 3\nchannels: 1\naverage: 1.5\n"
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        let value = serde_json::json!({
+            "1st": 1,
+            "2023-01": 2,
+            "a: b": 3,
+            "# not a comment": 4,
+            "title": "Part 1: the #1 video",
+            "multi": "first line\nsecond: line\n# third",
+            "url": "https://youtu.be/x?t=1#t",
+            "": 5,
+            "Yes": 6,
+        });
+
+        assert_eq!(
+            to_string(&value).unwrap(),
+            [
+                "\"1st\": 1",
+                "\"2023-01\": 2",
+                "\"a: b\": 3",
+                "\"# not a comment\": 4",
+                "title: \"Part 1: the #1 video\"",
+                "multi: \"first line\\nsecond: line\\n# third\"",
+                "url: \"https://youtu.be/x?t=1#t\"",
+                "\"\": 5",
+                "\"Yes\": 6",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}